@@ -0,0 +1,30 @@
+#![no_main]
+
+use std::path::Path;
+
+use libfuzzer_sys::fuzz_target;
+use ntro::dotenv::{infer::Inference, zod, Options};
+
+// Runs the whole pipeline (dotenv parsing, type hints, expansion, inference) over two files, the
+// second being a copy of the first half of the input, so that merging and conflicts get exercised.
+fuzz_target!(|source: &str| {
+    let half = source
+        .char_indices()
+        .map(|(i, _)| i)
+        .nth(source.chars().count() / 2)
+        .unwrap_or(source.len());
+
+    let sources = [source, &source[..half]]
+        .into_iter()
+        .enumerate()
+        .map(|(i, source)| zod::Metadata::new(source, Path::new(&format!(".env.{i}"))));
+
+    let _ = zod::generate_zod_schema_from_texts(
+        sources,
+        &Options {
+            expand: true,
+            infer: Some(Inference::Aggressive),
+            ..Default::default()
+        },
+    );
+});
@@ -0,0 +1,145 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+
+use super::typehint_parser::{is_type_name, rust_only_syntax};
+
+/// The config file looked for in the current directory, when none is given.
+pub const DEFAULT_CONFIG_FILE: &str = "ntro.config.json";
+
+/// The project configuration, e.g.
+///
+/// ```json
+/// {
+///   "types": {
+///     "semver": {
+///       "ts": "`${number}.${number}.${number}`",
+///       "pattern": "^\\d+\\.\\d+\\.\\d+$"
+///     }
+///   }
+/// }
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Custom types that `@type` hints can refer to by name.
+    #[serde(default)]
+    pub types: BTreeMap<String, CustomType>,
+}
+
+/// A type hint defined by the project, instead of ntro.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomType {
+    /// The type in `env.d.ts`, `string` by default.
+    pub ts: Option<String>,
+    /// The zod schema, a string matching the `pattern` by default.
+    pub zod: Option<String>,
+    /// A regex the values in the .env files are checked against.
+    pub pattern: Option<String>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Self> {
+        let config: Config = serde_json::from_str(text)?;
+
+        for (name, ty) in &config.types {
+            if !is_type_name(name) {
+                bail!(
+                    "invalid type name `{name}`, expected an identifier that isn't a built-in type"
+                );
+            }
+
+            if let Some(pattern) = &ty.pattern {
+                Regex::new(pattern)
+                    .with_context(|| format!("invalid pattern for the type `{name}`"))?;
+
+                if let Some((message, _, _)) = rust_only_syntax(pattern) {
+                    bail!("invalid pattern for the type `{name}`: {message}");
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("failed to read {path:?}"))?;
+
+        Self::parse(&text).with_context(|| format!("failed to parse {path:?}"))
+    }
+
+    /// The config file to read: the given one, or the default one if there is one.
+    pub fn path(path: Option<&Path>) -> Option<PathBuf> {
+        match path {
+            Some(path) => Some(path.to_path_buf()),
+            None => Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|path| path.exists()),
+        }
+    }
+
+    /// Reads the given config file, or the default one if there is one.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        match Self::path(path) {
+            Some(path) => Self::read(&path),
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dotenv::config::{Config, CustomType};
+
+    #[test]
+    fn parsing_config() {
+        let config = Config::parse(
+            r#"{
+                "types": {
+                    "semver": { "pattern": "^\\d+\\.\\d+\\.\\d+$" },
+                    "awsRegion": { "ts": "`${string}-${string}-${number}`", "zod": "z.string().min(1)" }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.types["semver"],
+            CustomType {
+                ts: None,
+                zod: None,
+                pattern: Some(r"^\d+\.\d+\.\d+$".to_string())
+            }
+        );
+        assert_eq!(
+            config.types["awsRegion"].zod.as_deref(),
+            Some("z.string().min(1)")
+        );
+
+        assert!(Config::parse("{}").unwrap().types.is_empty());
+
+        let err = |text: &str| format!("{:#}", Config::parse(text).unwrap_err());
+
+        assert_eq!(
+            err(r#"{ "types": { "string": {} } }"#),
+            "invalid type name `string`, expected an identifier that isn't a built-in type"
+        );
+        assert_eq!(
+            err(r#"{ "types": { "aws-region": {} } }"#),
+            "invalid type name `aws-region`, expected an identifier that isn't a built-in type"
+        );
+        assert!(err(r#"{ "types": { "slug": { "pattern": "[a-" } } }"#)
+            .starts_with("invalid pattern for the type `slug`: regex parse error"));
+        assert_eq!(
+            err(r#"{ "types": { "slug": { "pattern": "(?i)^[a-z]+$" } } }"#),
+            "invalid pattern for the type `slug`: unsupported inline flags `(?i)`, use flags after the closing `/` instead"
+        );
+        assert!(err(r#"{ "types": { "slug": { "regex": "^[a-z]+$" } } }"#)
+            .starts_with("unknown field `regex`, expected one of `ts`, `zod`, `pattern`"));
+    }
+}
@@ -0,0 +1,171 @@
+use std::fmt::Display;
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use thiserror::Error;
+
+use super::zod::Metadata;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found in one of the .env files, pointing at the exact spot in the source.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub meta: Metadata,
+    pub line: usize,
+    /// Byte offset into the line.
+    pub column: usize,
+    /// Number of bytes to underline, starting at `column`.
+    pub width: usize,
+}
+
+impl Diagnostic {
+    /// Locates `text` inside the source, starting the search at the byte `offset`.
+    /// Falls back to pointing at `offset` itself when the text can't be found.
+    pub fn at_text(
+        severity: Severity,
+        message: String,
+        meta: &Metadata,
+        offset: usize,
+        text: &str,
+    ) -> Self {
+        let start = meta.source[offset..]
+            .find(text)
+            .map(|i| offset + i)
+            .unwrap_or(offset);
+
+        let preceding = &meta.source[..start];
+        let line = preceding.matches('\n').count();
+        // columns are counted after the BOM, like the parser and the display do
+        let bom = match meta.source.starts_with('\u{feff}') {
+            true => '\u{feff}'.len_utf8(),
+            false => 0,
+        };
+        let line_start = preceding.rfind('\n').map(|i| i + 1).unwrap_or(bom);
+
+        Self {
+            severity,
+            message,
+            meta: meta.clone(),
+            line,
+            column: start - line_start,
+            width: text.len(),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self.severity {
+            Severity::Warning => "warning".yellow().bold(),
+            Severity::Error => "error".red().bold(),
+        };
+
+        let curr_line = self
+            .meta
+            .source
+            .lines()
+            .nth(self.line)
+            .unwrap_or_default()
+            .trim_start_matches('\u{feff}');
+
+        let column = self.column.min(curr_line.len());
+        let width = self.width.min(curr_line.len() - column);
+
+        let (before, highlighted, after) = match (
+            curr_line.get(..column),
+            curr_line.get(column..column + width),
+            curr_line.get(column + width..),
+        ) {
+            (Some(before), Some(highlighted), Some(after)) => (before, highlighted, after),
+            _ => (curr_line, "", ""),
+        };
+
+        let gutter = (self.line + 1).to_string();
+
+        writeln!(f, "{}: {}", label, self.message.bold())?;
+        writeln!(
+            f,
+            "{}",
+            format!(
+                "{}:{}:{}",
+                self.meta.path.to_string_lossy(),
+                self.line + 1,
+                before.chars().count() + 1
+            )
+            .dimmed()
+        )?;
+        writeln!(
+            f,
+            "  {}| {}{}{}",
+            gutter,
+            before,
+            highlighted.green().bold(),
+            after
+        )?;
+        write!(
+            f,
+            "  {}| {}{}",
+            " ".repeat(gutter.len()),
+            " ".repeat(before.chars().count()),
+            "^".repeat(highlighted.chars().count().max(1)).green()
+        )?;
+
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("{}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n\n"))]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+/// Logs every warning, and fails with all the errors if there are any.
+/// With `deny_warnings`, the warnings are treated as errors too.
+pub fn report(diagnostics: Vec<Diagnostic>, deny_warnings: bool) -> Result<()> {
+    let (errors, warnings): (Vec<_>, Vec<_>) = diagnostics
+        .into_iter()
+        .partition(|d| deny_warnings || d.severity == Severity::Error);
+
+    for warning in warnings {
+        log::warn!("\n{warning}");
+    }
+
+    if !errors.is_empty() {
+        let count = errors.len();
+        return Err(Diagnostics(errors))
+            .context(format!("found {count} error(s) in the .env files"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::dotenv::{
+        diagnostic::{Diagnostic, Severity},
+        zod::Metadata,
+    };
+
+    #[test]
+    fn locating_text_after_a_bom() {
+        let meta = Metadata::new("\u{feff}PORT=abc\nHOST=abc\n", Path::new(".env"));
+        let at = |offset| {
+            let d = Diagnostic::at_text(Severity::Warning, String::new(), &meta, offset, "abc");
+            (d.line, d.column, d.width)
+        };
+
+        assert_eq!(at(0), (0, 5, 3));
+        assert_eq!(at(12), (1, 5, 3));
+
+        let display = Diagnostic::at_text(Severity::Warning, "bad".into(), &meta, 0, "abc");
+        assert!(display.to_string().contains(".env:1:6"));
+    }
+}
@@ -0,0 +1,488 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
+use super::{
+    diagnostic::{Diagnostic, Severity},
+    parse::Variable,
+    zod::Metadata,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Fallback<'value> {
+    /// `${NAME:-fallback}`
+    IfUnsetOrEmpty(&'value str),
+    /// `${NAME-fallback}`
+    IfUnset(&'value str),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Reference<'value> {
+    name: &'value str,
+    fallback: Option<Fallback<'value>>,
+    /// Byte range of the whole reference (e.g. `${NAME:-fallback}`) in the value.
+    span: Range<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Segment<'value> {
+    Text(&'value str),
+    Reference(Reference<'value>),
+}
+
+fn is_ident_start(c: u8) -> bool {
+    c.is_ascii_alphabetic() || c == b'_'
+}
+
+fn is_ident(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+/// Splits a value into literal text and references, like dotenv-expand does.
+/// `\$` escapes a reference and becomes a literal `$`.
+fn segments(value: &str) -> Vec<Segment<'_>> {
+    let bytes = value.as_bytes();
+    let mut segments = Vec::new();
+    let mut text_start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if bytes.get(i + 1) == Some(&b'$') => {
+                segments.push(Segment::Text(&value[text_start..i]));
+                text_start = i + 1;
+                i += 2;
+            }
+            b'$' => match reference_at(value, i) {
+                Some(reference) => {
+                    segments.push(Segment::Text(&value[text_start..i]));
+                    i = reference.span.end;
+                    text_start = i;
+                    segments.push(Segment::Reference(reference));
+                }
+                None => i += 1,
+            },
+            _ => i += 1,
+        }
+    }
+
+    segments.push(Segment::Text(&value[text_start..]));
+    segments.retain(|s| s != &Segment::Text(""));
+    segments
+}
+
+fn reference_at(value: &str, start: usize) -> Option<Reference<'_>> {
+    let bytes = value.as_bytes();
+    let ident_end = |from: usize| {
+        from + bytes[from..]
+            .iter()
+            .position(|&c| !is_ident(c))
+            .unwrap_or(bytes.len() - from)
+    };
+
+    match bytes.get(start + 1) {
+        Some(b'{') if bytes.get(start + 2).copied().is_some_and(is_ident_start) => {
+            let name_end = ident_end(start + 2);
+            let name = &value[start + 2..name_end];
+
+            let (if_empty, fallback_start) = match &bytes[name_end..] {
+                [b'}', ..] => {
+                    return Some(Reference {
+                        name,
+                        fallback: None,
+                        span: start..name_end + 1,
+                    })
+                }
+                [b':', b'-', ..] => (true, name_end + 2),
+                [b'-', ..] => (false, name_end + 1),
+                _ => return None,
+            };
+
+            let close = fallback_start + value[fallback_start..].find('}')?;
+            let fallback = &value[fallback_start..close];
+
+            Some(Reference {
+                name,
+                fallback: Some(if if_empty {
+                    Fallback::IfUnsetOrEmpty(fallback)
+                } else {
+                    Fallback::IfUnset(fallback)
+                }),
+                span: start..close + 1,
+            })
+        }
+        Some(&c) if is_ident_start(c) => {
+            let name_end = ident_end(start + 1);
+
+            Some(Reference {
+                name: &value[start + 1..name_end],
+                fallback: None,
+                span: start..name_end,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// All the references in a value, including the ones in fallbacks.
+fn references(value: &str) -> Vec<Reference<'_>> {
+    segments(value)
+        .into_iter()
+        .flat_map(|segment| match segment {
+            Segment::Text(_) => vec![],
+            Segment::Reference(reference) => {
+                let mut nested = match reference.fallback {
+                    Some(Fallback::IfUnsetOrEmpty(f) | Fallback::IfUnset(f)) => references(f),
+                    None => vec![],
+                };
+                nested.insert(0, reference);
+                nested
+            }
+        })
+        .collect()
+}
+
+struct Expander<'a> {
+    definitions: &'a HashMap<&'a str, &'a str>,
+    resolved: HashMap<&'a str, String>,
+    resolving: Vec<&'a str>,
+}
+
+impl<'a> Expander<'a> {
+    fn resolve(&mut self, name: &'a str) -> Option<String> {
+        if let Some(value) = self.resolved.get(name) {
+            return Some(value.clone());
+        }
+
+        let raw = *self.definitions.get(name)?;
+
+        // a cycle, which is reported separately
+        if self.resolving.contains(&name) {
+            return Some(String::new());
+        }
+
+        self.resolving.push(name);
+        let value = self.expand(raw);
+        self.resolving.pop();
+
+        self.resolved.insert(name, value.clone());
+
+        Some(value)
+    }
+
+    fn expand(&mut self, value: &'a str) -> String {
+        segments(value)
+            .into_iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.to_string(),
+                Segment::Reference(reference) => {
+                    match (self.resolve(reference.name), reference.fallback) {
+                        (Some(v), Some(Fallback::IfUnsetOrEmpty(fallback))) if v.is_empty() => {
+                            self.expand(fallback)
+                        }
+                        (None, Some(Fallback::IfUnsetOrEmpty(f) | Fallback::IfUnset(f))) => {
+                            self.expand(f)
+                        }
+                        (v, _) => v.unwrap_or_default(),
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// Names a variable depends on, given which variables are defined. References in a fallback
+/// only count when the variable they stand in for isn't defined.
+fn dependencies<'v>(value: &'v str, definitions: &HashMap<&str, &str>) -> Vec<Reference<'v>> {
+    segments(value)
+        .into_iter()
+        .flat_map(|segment| match segment {
+            Segment::Text(_) => vec![],
+            Segment::Reference(reference) => match reference.fallback {
+                Some(Fallback::IfUnsetOrEmpty(f) | Fallback::IfUnset(f))
+                    if !definitions.contains_key(reference.name) =>
+                {
+                    dependencies(f, definitions)
+                }
+                _ => vec![reference],
+            },
+        })
+        .collect()
+}
+
+fn find_cycles(
+    variables: &[(Variable, Metadata)],
+    effective: &HashMap<&str, usize>,
+    definitions: &HashMap<&str, &str>,
+) -> Vec<Diagnostic> {
+    fn visit(
+        index: usize,
+        variables: &[(Variable, Metadata)],
+        effective: &HashMap<&str, usize>,
+        definitions: &HashMap<&str, &str>,
+        path: &mut Vec<usize>,
+        done: &mut HashSet<usize>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        if done.contains(&index) {
+            return;
+        }
+
+        path.push(index);
+
+        let (var, meta) = &variables[index];
+
+        for reference in dependencies(&var.value, definitions) {
+            let Some(&next) = effective.get(reference.name) else {
+                continue;
+            };
+
+            if let Some(cycle_start) = path.iter().position(|&i| i == next) {
+                let chain = path[cycle_start..]
+                    .iter()
+                    .chain([&next])
+                    .map(|&i| variables[i].0.key.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+
+                diagnostics.push(Diagnostic::at_text(
+                    Severity::Error,
+                    format!(
+                        "`{}` can't be expanded, it refers back to itself: {chain}",
+                        var.key
+                    ),
+                    meta,
+                    var.span.start,
+                    &var.value[reference.span],
+                ));
+                continue;
+            }
+
+            visit(
+                next,
+                variables,
+                effective,
+                definitions,
+                path,
+                done,
+                diagnostics,
+            );
+        }
+
+        path.pop();
+        done.insert(index);
+    }
+
+    let mut diagnostics = Vec::new();
+    let mut done = HashSet::new();
+
+    let mut roots = effective.values().copied().collect::<Vec<_>>();
+    roots.sort();
+
+    for index in roots {
+        visit(
+            index,
+            variables,
+            effective,
+            definitions,
+            &mut vec![],
+            &mut done,
+            &mut diagnostics,
+        );
+    }
+
+    diagnostics
+}
+
+/// Expands `${NAME}`, `$NAME`, `${NAME:-fallback}` and `${NAME-fallback}` references in the value
+/// of every variable, resolving them against the variables across all the files. Like when dotenv
+/// loads several files, the first file to define a variable wins.
+///
+/// References to variables that aren't defined anywhere are reported as warnings, since they may
+/// still be set in the environment at runtime, and cycles are reported as errors.
+pub fn expand_variables(variables: &mut [(Variable, Metadata)]) -> Vec<Diagnostic> {
+    let mut effective: HashMap<&str, usize> = HashMap::new();
+
+    for (index, (var, _)) in variables.iter().enumerate() {
+        effective.entry(var.key.as_str()).or_insert(index);
+    }
+
+    let definitions = effective
+        .iter()
+        .map(|(&key, &index)| (key, variables[index].0.value.as_str()))
+        .collect::<HashMap<_, _>>();
+
+    let mut diagnostics = Vec::new();
+
+    for (var, meta) in variables.iter() {
+        for reference in references(&var.value) {
+            if reference.fallback.is_none() && !definitions.contains_key(reference.name) {
+                diagnostics.push(Diagnostic::at_text(
+                    Severity::Warning,
+                    format!(
+                        "`{}` isn't defined in any of the .env files, so it has to be set in the environment",
+                        reference.name
+                    ),
+                    meta,
+                    var.span.start,
+                    &var.value[reference.span],
+                ));
+            }
+        }
+    }
+
+    diagnostics.extend(find_cycles(variables, &effective, &definitions));
+
+    let mut expander = Expander {
+        definitions: &definitions,
+        resolved: HashMap::new(),
+        resolving: Vec::new(),
+    };
+
+    let expanded = variables
+        .iter()
+        .map(|(var, _)| {
+            expander.resolving.push(var.key.as_str());
+            let value = expander.expand(&var.value);
+            expander.resolving.pop();
+            value
+        })
+        .collect::<Vec<_>>();
+
+    for ((var, _), value) in variables.iter_mut().zip(expanded) {
+        var.value = value;
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::dotenv::{
+        diagnostic::Severity,
+        expand::{expand_variables, segments, Fallback, Reference, Segment},
+        parse::parse_variables,
+        zod::Metadata,
+    };
+
+    type Expanded = (Vec<(String, String)>, Vec<(Severity, String, usize)>);
+
+    fn expand(sources: &[&str]) -> Expanded {
+        let mut variables = sources
+            .iter()
+            .enumerate()
+            .flat_map(|(i, source)| {
+                let meta = Metadata {
+                    source: (*source).into(),
+                    path: Path::new(&format!(".env.{i}")).into(),
+                };
+                parse_variables(source)
+                    .unwrap()
+                    .into_iter()
+                    .map(move |var| (var, meta.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        let diagnostics = expand_variables(&mut variables);
+
+        (
+            variables
+                .into_iter()
+                .map(|(var, _)| (var.key, var.value))
+                .collect(),
+            diagnostics
+                .into_iter()
+                .map(|d| (d.severity, d.message, d.line))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn splitting_values_into_references() {
+        assert_eq!(
+            segments("a${B}$C_1 \\$D ${E:-e ${F}} ${G-} $ ${ $1"),
+            vec![
+                Segment::Text("a"),
+                Segment::Reference(Reference {
+                    name: "B",
+                    fallback: None,
+                    span: 1..5
+                }),
+                Segment::Reference(Reference {
+                    name: "C_1",
+                    fallback: None,
+                    span: 5..9
+                }),
+                Segment::Text(" "),
+                Segment::Text("$D "),
+                Segment::Reference(Reference {
+                    name: "E",
+                    fallback: Some(Fallback::IfUnsetOrEmpty("e ${F")),
+                    span: 14..25
+                }),
+                Segment::Text("} "),
+                Segment::Reference(Reference {
+                    name: "G",
+                    fallback: Some(Fallback::IfUnset("")),
+                    span: 27..32
+                }),
+                Segment::Text(" $ ${ $1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn expanding_across_files() {
+        let (values, diagnostics) = expand(&[
+            "HOST=localhost\nURL=\"http://${HOST}:$PORT/${PATH_PREFIX:-api}\"\nEMPTY=\n",
+            "PORT=3000\nHOST=ignored\nFALLBACKS=${EMPTY:-a}${EMPTY-b}${UNSET-c}\nESCAPED=\\$HOST\n",
+        ]);
+
+        assert_eq!(
+            values,
+            [
+                ("HOST", "localhost"),
+                ("URL", "http://localhost:3000/api"),
+                ("EMPTY", ""),
+                ("PORT", "3000"),
+                ("HOST", "ignored"),
+                ("FALLBACKS", "ac"),
+                ("ESCAPED", "$HOST"),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn reporting_unknown_references_and_cycles() {
+        let (_, diagnostics) = expand(&[
+            "A=${B}\nHOME_DIR=$HOME\n",
+            "B=${C:-x}\nC=$A\nSELF=${SELF}\nOK=${UNSET:-$A}\n",
+        ]);
+
+        assert_eq!(
+            diagnostics,
+            vec![
+                (
+                    Severity::Warning,
+                    "`HOME` isn't defined in any of the .env files, so it has to be set in the environment".to_string(),
+                    1
+                ),
+                (
+                    Severity::Error,
+                    "`C` can't be expanded, it refers back to itself: A -> B -> C -> A".to_string(),
+                    1
+                ),
+                (
+                    Severity::Error,
+                    "`SELF` can't be expanded, it refers back to itself: SELF -> SELF".to_string(),
+                    2
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,52 @@
+const processEnv: Record<string, string | undefined> = {};
+
+/* --- MAIN IMPLEMENTATION BELOW --- */
+
+const REFERENCE =
+  /\\\$|\$(?:\{([A-Za-z_][A-Za-z0-9_]*)(?:(:?-)([^}]*))?\}|([A-Za-z_][A-Za-z0-9_]*))/g;
+
+function readEnv(prop: string, resolving: string[] = []): string | undefined {
+  const value =
+    prop in processEnv
+      ? processEnv[prop as keyof typeof processEnv]
+      : process.env[prop];
+
+  if (value === undefined) {
+    return value;
+  }
+
+  // a cycle, which ntro reports when generating this module
+  if (resolving.includes(prop)) {
+    return "";
+  }
+
+  return expand(value, [...resolving, prop]);
+}
+
+function expand(value: string, resolving: string[]): string {
+  return value.replace(
+    REFERENCE,
+    (
+      match: string,
+      braced?: string,
+      operator?: string,
+      fallback?: string,
+      bare?: string
+    ) => {
+      if (match === "\\$") {
+        return "$";
+      }
+
+      const resolved = readEnv(braced ?? bare ?? "", resolving);
+
+      if (
+        fallback !== undefined &&
+        (resolved === undefined || (operator === ":-" && resolved === ""))
+      ) {
+        return expand(fallback, resolving);
+      }
+
+      return resolved ?? "";
+    }
+  );
+}
@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+
+use super::{
+    parse::Variable,
+    typehint_parser::{Format, Literal, TypeHint},
+};
+
+/// How far to go when inferring the type of a variable without a type hint, from its values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum Inference {
+    /// Only infer numbers and booleans.
+    Conservative,
+    /// Also infer urls, and unions of the literal values found across the files.
+    Aggressive,
+}
+
+/// The most literals an inferred union can have, before it's just considered a string.
+const MAX_UNION_SIZE: usize = 8;
+const MAX_LITERAL_LENGTH: usize = 32;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Kind {
+    Number,
+    Boolean,
+    Url,
+    String,
+}
+
+fn is_number(value: &str) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, "0"));
+
+    let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit());
+
+    // a leading zero (e.g. a zip code) would be lost by parsing it as a number
+    all_digits(whole) && all_digits(fraction) && (whole == "0" || !whole.starts_with('0'))
+}
+
+pub(super) fn is_url(value: &str) -> bool {
+    let Some((scheme, rest)) = value.split_once("://") else {
+        return false;
+    };
+
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '-'))
+        && !rest.is_empty()
+        && !rest.contains(char::is_whitespace)
+}
+
+fn is_literal(value: &str) -> bool {
+    value.len() <= MAX_LITERAL_LENGTH
+        && value
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, b'_' | b'-' | b'.'))
+}
+
+fn kind_of(value: &str, inference: Inference) -> Kind {
+    match value {
+        "true" | "false" => Kind::Boolean,
+        v if is_number(v) => Kind::Number,
+        v if inference >= Inference::Aggressive && is_url(v) => Kind::Url,
+        _ => Kind::String,
+    }
+}
+
+/// Values that disagree on their kind widen to a string.
+fn widen(a: Kind, b: Kind) -> Kind {
+    if a == b {
+        a
+    } else {
+        Kind::String
+    }
+}
+
+fn literal_union(values: &[&str]) -> Option<TypeHint> {
+    let mut distinct: Vec<&str> = Vec::new();
+
+    for value in values {
+        if !distinct.contains(value) {
+            distinct.push(value);
+        }
+    }
+
+    if distinct.len() < 2
+        || distinct.len() > MAX_UNION_SIZE
+        || !distinct.iter().all(|v| is_literal(v))
+    {
+        return None;
+    }
+
+    Some(TypeHint::Union(
+        distinct
+            .iter()
+            .map(|&v| Literal::String(v.into()))
+            .collect(),
+    ))
+}
+
+fn infer(values: &[&str], inference: Inference) -> Option<TypeHint> {
+    let kind = values
+        .iter()
+        .map(|value| kind_of(value, inference))
+        .reduce(widen)?;
+
+    match kind {
+        Kind::Number => Some(TypeHint::NUMBER),
+        // literals rather than `boolean`, which would be coerced to true for `false`
+        Kind::Boolean => Some(TypeHint::Union(
+            [Literal::Boolean(true), Literal::Boolean(false)].into(),
+        )),
+        Kind::Url => Some(TypeHint::Format(Format::Url)),
+        Kind::String if inference >= Inference::Aggressive => literal_union(values),
+        Kind::String => None,
+    }
+}
+
+/// Proposes a type hint for each variable whose (non empty) values, across all the files, are more
+/// specific than just any string.
+pub fn infer_type_hints<'v>(
+    variables: impl IntoIterator<Item = &'v Variable>,
+    inference: Inference,
+) -> BTreeMap<String, TypeHint> {
+    let mut values: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+
+    for var in variables {
+        if !var.value.is_empty() {
+            values.entry(&var.key).or_default().push(&var.value);
+        }
+    }
+
+    values
+        .into_iter()
+        .filter_map(|(key, values)| infer(&values, inference).map(|th| (key.to_string(), th)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dotenv::{
+        infer::{infer, Inference},
+        typehint_parser::{Format, Literal, TypeHint},
+    };
+
+    #[test]
+    fn inferring_type_hints_from_values() {
+        let conservative = |values: &[&str]| infer(values, Inference::Conservative);
+        let aggressive = |values: &[&str]| infer(values, Inference::Aggressive);
+
+        assert_eq!(conservative(&["3000"]), Some(TypeHint::NUMBER));
+        assert_eq!(conservative(&["-1.5", "0", "0.25"]), Some(TypeHint::NUMBER));
+        assert_eq!(
+            conservative(&["true", "false"]),
+            Some(TypeHint::Union(
+                [Literal::Boolean(true), Literal::Boolean(false)].into()
+            ))
+        );
+        assert_eq!(conservative(&["https://example.com"]), None);
+        assert_eq!(conservative(&["development", "production"]), None);
+        assert_eq!(conservative(&["01234"]), None);
+        assert_eq!(conservative(&["1e5"]), None);
+
+        assert_eq!(
+            aggressive(&["https://example.com", "postgres://user@localhost:5432/db"]),
+            Some(TypeHint::Format(Format::Url))
+        );
+        assert_eq!(
+            aggressive(&["development", "production", "development"]),
+            Some(TypeHint::Union(
+                ["development", "production"]
+                    .map(|v| Literal::String(v.into()))
+                    .into()
+            ))
+        );
+        assert_eq!(aggressive(&["development"]), None);
+        assert_eq!(aggressive(&["some value", "another one"]), None);
+
+        // conflicts between files widen the type
+        assert_eq!(conservative(&["3000", "true"]), None);
+        assert_eq!(aggressive(&["https://example.com", "3000"]), None);
+        assert_eq!(
+            aggressive(&["3000", "auto"]),
+            Some(TypeHint::Union(
+                ["3000", "auto"].map(|v| Literal::String(v.into())).into()
+            ))
+        );
+    }
+}
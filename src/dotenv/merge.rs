@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+
+use super::{
+    parse::Variable,
+    typehint_parser::TypeHint,
+    zod::{Metadata, ParseError, TypeHintAt},
+};
+
+/// What to do when the .env files disagree on the type hint of a variable.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OnConflict {
+    /// Fail, pointing at both type hints.
+    #[default]
+    Error,
+    /// Merge unions of literals, e.g. `'dev'` and `'prod'` into `'dev' | 'prod'`, and fail on
+    /// anything else.
+    Union,
+    /// Keep the type hint of the first file.
+    First,
+    /// Keep the type hint of the last file.
+    Last,
+}
+
+/// A variable across the files, with its type hint once merged.
+#[derive(Debug)]
+pub struct MergedVariable<'v> {
+    /// The assignment the variable is reported from.
+    pub var: &'v Variable,
+    pub type_hint: Option<TypeHint>,
+    /// The assignments whose type hints make up the merged one.
+    pub sources: Vec<&'v (Variable, Metadata)>,
+}
+
+impl MergedVariable<'_> {
+    /// Where the type hint comes from, e.g. `from ".env" on line 2`, listing every file for a
+    /// merged one.
+    pub fn origin(&self) -> String {
+        let sources = self
+            .sources
+            .iter()
+            .filter_map(|(var, meta)| {
+                let (_, line) = var.type_hint.as_ref()?;
+                Some(format!("{:?} on line {}", meta.path, line + 1))
+            })
+            .collect::<Vec<_>>();
+
+        match &sources[..] {
+            [source] => format!("from {source}"),
+            sources => format!("merged from {}", sources.join(", ")),
+        }
+    }
+}
+
+/// The union of two type hints, when both are unions of literals, possibly optional.
+fn union_of(a: &TypeHint, b: &TypeHint) -> Option<TypeHint> {
+    match (a, b) {
+        _ if a == b => Some(a.clone()),
+        (TypeHint::Optional(a), b) => union_of(a, b).map(TypeHint::optional),
+        (a, TypeHint::Optional(b)) => union_of(a, b).map(TypeHint::optional),
+        (TypeHint::Union(a), TypeHint::Union(b)) => Some(TypeHint::Union(
+            a.iter()
+                .chain(b.iter().filter(|literal| !a.contains(literal)))
+                .cloned()
+                .collect(),
+        )),
+        _ => None,
+    }
+}
+
+/// Merges the variables of every file by name, resolving the type hints they disagree on
+/// following `on_conflict`.
+pub fn merge_variables(
+    variables: &[(Variable, Metadata)],
+    on_conflict: OnConflict,
+) -> Result<BTreeMap<&str, MergedVariable<'_>>> {
+    let mut merged: BTreeMap<&str, MergedVariable> = BTreeMap::new();
+
+    for entry in variables {
+        let (var, meta) = entry;
+
+        let Some(existing) = merged.get_mut(var.key.as_str()) else {
+            merged.insert(
+                &var.key,
+                MergedVariable {
+                    var,
+                    type_hint: var.type_hint.as_ref().map(|(th, _)| th.clone()),
+                    sources: var.type_hint.iter().map(|_| entry).collect(),
+                },
+            );
+            continue;
+        };
+
+        let Some((th, _)) = &var.type_hint else {
+            continue;
+        };
+
+        let conflict = |context: &'static str| {
+            let (o_var, o_meta) = existing.sources[0];
+
+            Err(ParseError::ConflictingTypes {
+                a: TypeHintAt::new(o_meta, o_var),
+                b: TypeHintAt::new(meta, var),
+            })
+            .context(context)
+        };
+
+        match &existing.type_hint {
+            None => {
+                *existing = MergedVariable {
+                    var,
+                    type_hint: Some(th.clone()),
+                    sources: vec![entry],
+                };
+            }
+            Some(existing_th) if existing_th == th => {}
+            Some(existing_th) => match on_conflict {
+                OnConflict::Error => {
+                    return conflict(
+                        "found some conflicting types while parsing variables with type hints",
+                    )
+                }
+                OnConflict::First => {}
+                OnConflict::Last => {
+                    *existing = MergedVariable {
+                        var,
+                        type_hint: Some(th.clone()),
+                        sources: vec![entry],
+                    };
+                }
+                OnConflict::Union => match union_of(existing_th, th) {
+                    Some(union) => {
+                        existing.type_hint = Some(union);
+                        existing.sources.push(entry);
+                    }
+                    None => {
+                        return conflict(
+                            "found some conflicting types that can't be merged into a union",
+                        )
+                    }
+                },
+            },
+        }
+    }
+
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::dotenv::{
+        merge::{merge_variables, OnConflict},
+        parse::{parse_variables_with_type_hints, Variable},
+        typehint_parser::TypeDefs,
+        zod::Metadata,
+    };
+
+    fn variables(sources: &[&str]) -> Vec<(Variable, Metadata)> {
+        sources
+            .iter()
+            .enumerate()
+            .flat_map(|(i, source)| {
+                let meta = Metadata::new(source, Path::new(&format!(".env.{i}")));
+
+                parse_variables_with_type_hints(&meta, &TypeDefs::new(), &mut vec![])
+                    .unwrap()
+                    .into_iter()
+                    .map(move |var| (var, meta.clone()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merging_conflicting_type_hints() {
+        let variables = variables(&[
+            "# @type 'dev' | 'qa'\nSTAGE=dev\n# @type int\nPORT=3000\n# @type string\nNAME=\n",
+            "# @type 'prod' | 'qa'?\nSTAGE=prod\nPORT=80\n# @type 'ntro'\nNAME=\n",
+        ]);
+
+        let merged = |on_conflict| {
+            merge_variables(&variables, on_conflict).map(|merged| {
+                merged
+                    .values()
+                    .map(|m| {
+                        let th = m.type_hint.as_ref().map(ToString::to_string);
+                        (m.var.key.as_str(), th, m.origin())
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
+        let err = |on_conflict| format!("{:#}", merged(on_conflict).unwrap_err());
+
+        assert!(err(OnConflict::Error)
+            .starts_with("found some conflicting types while parsing variables with type hints"));
+        assert!(err(OnConflict::Union)
+            .starts_with("found some conflicting types that can't be merged into a union"));
+
+        assert_eq!(
+            merged(OnConflict::First).unwrap(),
+            vec![
+                (
+                    "NAME",
+                    Some("string".to_string()),
+                    "from \".env.0\" on line 5".to_string()
+                ),
+                (
+                    "PORT",
+                    Some("int".to_string()),
+                    "from \".env.0\" on line 3".to_string()
+                ),
+                (
+                    "STAGE",
+                    Some("'dev' | 'qa'".to_string()),
+                    "from \".env.0\" on line 1".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            merged(OnConflict::Last).unwrap(),
+            vec![
+                (
+                    "NAME",
+                    Some("'ntro'".to_string()),
+                    "from \".env.1\" on line 4".to_string()
+                ),
+                (
+                    "PORT",
+                    Some("int".to_string()),
+                    "from \".env.0\" on line 3".to_string()
+                ),
+                (
+                    "STAGE",
+                    Some("'prod' | 'qa'?".to_string()),
+                    "from \".env.1\" on line 1".to_string()
+                ),
+            ]
+        );
+
+        let variables = self::variables(&[
+            "# @type 'dev' | 'qa'\nSTAGE=dev\n",
+            "# @type 'prod' | 'qa'?\nSTAGE=prod\n",
+            "STAGE=qa\n",
+        ]);
+        let merged = merge_variables(&variables, OnConflict::Union).unwrap();
+
+        assert_eq!(
+            merged["STAGE"].type_hint.as_ref().map(ToString::to_string),
+            Some("'dev' | 'qa' | 'prod'?".to_string())
+        );
+        assert_eq!(
+            merged["STAGE"].origin(),
+            "merged from \".env.0\" on line 1, \".env.1\" on line 1"
+        );
+    }
+}
@@ -1,44 +1,225 @@
 use anyhow::{Context, Result};
-use std::{
-    collections::BTreeSet,
-    fs::File,
-    io::{BufReader, Read},
-    path::PathBuf,
-};
+use std::{collections::BTreeMap, path::PathBuf};
 
-use self::parse::parse_variables;
+use self::{
+    config::Config,
+    expand::expand_variables,
+    infer::{infer_type_hints, Inference},
+    merge::{merge_variables, OnConflict},
+    parse::{descriptions, get_texts, parse_typedefs, parse_variables_with_type_hints, Variable},
+    requirement::{requirements, Requirement},
+    typehint_parser::{Format, JsonShape, Literal, Transform, TypeHint},
+    validate::validate_values,
+    zod::Metadata,
+};
 
-mod parse;
+pub mod config;
+mod diagnostic;
+mod expand;
+pub mod infer;
+pub mod merge;
+pub mod parse;
+pub mod profile;
+pub mod requirement;
 
 mod typehint_parser;
+mod validate;
 pub mod zod;
 
-pub fn generate_typescript_types(files: &[PathBuf]) -> Result<String> {
-    let vars = files
-        .iter()
-        .map(|file| {
-            File::open(file)
-                .map(BufReader::new)
-                .and_then(|mut rdr| {
-                    let mut buf = String::new();
-                    rdr.read_to_string(&mut buf).map(|_| buf)
+/// Options for generating code from .env files.
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// Import dotenv in the generated module, so that it loads the .env files into `process.env`.
+    pub import_dotenv: bool,
+    /// Resolve `${VAR}` references between the variables, and expand them at runtime in the
+    /// generated module.
+    pub expand: bool,
+    /// Infer the types of variables without a type hint from their values.
+    pub infer: Option<Inference>,
+    /// Fail on warnings, like malformed type hints, instead of just logging them.
+    pub deny_warnings: bool,
+    /// The project's configuration, e.g. its custom types.
+    pub config: Config,
+    /// What to do when the .env files disagree on the type hint of a variable.
+    pub on_conflict: OnConflict,
+    /// Layer the .env files like dotenv-flow and Next.js do, e.g. `.env.production.local` over
+    /// `.env.local` over `.env.production` over `.env`, and know which variables each mode sets.
+    pub profiles: bool,
+}
+
+/// Reads and loads the variables of every .env file, see [load_variables].
+pub fn read_variables(files: &[PathBuf], options: &Options) -> Result<Vec<(Variable, Metadata)>> {
+    let texts = get_texts(files);
+
+    load_variables(
+        texts.iter().map(|(text, file)| Metadata::new(text, file)),
+        options,
+    )
+}
+
+/// Parses the variables of every .env file along with their type hints, expands their references
+/// when asked to, and reports whatever problems were found along the way.
+pub fn load_variables(
+    sources: impl Iterator<Item = Metadata>,
+    options: &Options,
+) -> Result<Vec<(Variable, Metadata)>> {
+    let mut variables = Vec::new();
+    let mut diagnostics = Vec::new();
+
+    let sources = sources.collect::<Vec<_>>();
+    let typedefs = parse_typedefs(&sources, &options.config.types, &mut diagnostics)?;
+
+    for meta in sources {
+        let vars = parse_variables_with_type_hints(&meta, &typedefs, &mut diagnostics)
+            .with_context(|| format!("failed to parse {:?}", meta.path))?;
+
+        variables.extend(vars.into_iter().map(|var| (var, meta.clone())));
+    }
+
+    if options.expand {
+        diagnostics.extend(expand_variables(&mut variables));
+    }
+
+    diagnostics.extend(validate_values(&variables, options.on_conflict));
+
+    diagnostic::report(diagnostics, options.deny_warnings)?;
+
+    Ok(variables)
+}
+
+/// A template literal type for the template literal type hints, a union of string literals for
+/// the unions, and the string formats typescript can express.
+fn typescript_type(th: &TypeHint) -> Option<String> {
+    match th {
+        TypeHint::Optional(th)
+        | TypeHint::Defaulted(th, _)
+        | TypeHint::Matching(th, _)
+        | TypeHint::Named(_, th) => typescript_type(th),
+        TypeHint::Template(_) => Some(th.to_string()),
+        // process.env only ever holds strings, e.g. `'8080'` rather than `8080`
+        TypeHint::Union(literals) => Some(
+            literals
+                .iter()
+                .map(|literal| match literal {
+                    Literal::String(_) => literal.to_string(),
+                    literal => format!("'{literal}'"),
                 })
-                .context(format!("failed read {file:?}"))
-                .map(|text| {
-                    parse_variables(&text)
-                        .iter()
-                        .map(|i| i.to_string())
-                        .collect::<Vec<_>>()
+                .collect::<Vec<_>>()
+                .join(" | "),
+        ),
+        TypeHint::Custom(_, ty) => ty.ts.clone(),
+        TypeHint::Format(Format::Url) => Some("`${string}://${string}`".to_string()),
+        TypeHint::Format(Format::Email) => Some("`${string}@${string}`".to_string()),
+        TypeHint::Format(Format::Uuid) => {
+            Some("`${string}-${string}-${string}-${string}-${string}`".to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Describes the shape of the values that a string type can't express.
+fn typescript_doc(th: &TypeHint) -> Option<String> {
+    match th {
+        TypeHint::Optional(th) | TypeHint::Named(_, th) => typescript_doc(th),
+        TypeHint::Defaulted(th, default) => Some(match typescript_doc(th) {
+            Some(doc) => format!("{doc} Defaults to `{default}`."),
+            None => format!("Defaults to `{default}`."),
+        }),
+        TypeHint::Array { element, separator } => Some(format!(
+            "A list of `{element}`, separated by `{separator}`."
+        )),
+        TypeHint::Date { time: false, .. } => Some("A date, like `2024-01-31`.".to_string()),
+        TypeHint::Date { time: true, .. } => {
+            Some("A timestamp, like `2024-01-31T09:00:00Z`.".to_string())
+        }
+        TypeHint::Quantity { quantity, unit } => Some(format!(
+            "Parsed from {}, into a number of `{unit}`.",
+            quantity.describe()
+        )),
+        TypeHint::Json(JsonShape::Unknown) => Some("A JSON document.".to_string()),
+        TypeHint::Json(shape) => Some(format!("A JSON document, of type `{shape}`.")),
+        TypeHint::Transformed(th, transforms) => {
+            let split = transforms.iter().any(|t| matches!(t, Transform::Split(_)));
+            let transforms = transforms
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            Some(match split {
+                true => format!(
+                    "Transformed with `{transforms}`, before each part is parsed as `{th}`."
+                ),
+                false => format!("Transformed with `{transforms}`, before being parsed as `{th}`."),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Whether the key can be written as is in JavaScript, as opposed to e.g. `APP.NAME` or `my-key`.
+fn is_identifier(key: &str) -> bool {
+    key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The key as a property name, quoted unless it's an identifier.
+fn property_name(key: &str) -> String {
+    match is_identifier(key) {
+        true => key.to_string(),
+        false => serde_json::Value::from(key).to_string(),
+    }
+}
+
+/// Reads the key from `process.env`, with a dot when it's an identifier so that bundlers, e.g.
+/// Next.js, can inline it.
+fn process_env(key: &str) -> String {
+    match is_identifier(key) {
+        true => format!("process.env.{key}"),
+        false => format!("process.env[{}]", property_name(key)),
+    }
+}
+
+/// A JSDoc comment, on a single line unless the text spans several, indented by `indent`.
+fn jsdoc(text: &str, indent: &str) -> String {
+    let text = text.replace("*/", "*\\/");
+
+    match text.contains('\n') {
+        false => format!("{indent}/** {text} */"),
+        true => format!(
+            "{indent}/**\n{}\n{indent} */",
+            text.lines()
+                .map(|line| match line.is_empty() {
+                    true => format!("{indent} *"),
+                    false => format!("{indent} * {line}"),
                 })
-        })
-        .filter_map(|result| {
-            if let Err(e) = &result {
-                log::error!("{e:?}");
-            }
-            result.ok()
-        })
-        .flatten()
-        .collect::<BTreeSet<_>>();
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+    }
+}
+
+pub fn generate_typescript_types(files: &[PathBuf], options: &Options) -> Result<String> {
+    generate_typescript_types_from_variables(&read_variables(files, options)?, options)
+}
+
+pub fn generate_typescript_types_from_variables(
+    variables: &[(Variable, Metadata)],
+    options: &Options,
+) -> Result<String> {
+    let inferred = options
+        .infer
+        .map(|inference| infer_type_hints(variables.iter().map(|(var, _)| var), inference))
+        .unwrap_or_default();
+
+    // the type hints are merged as in the zod schema, failing on the same conflicts
+    let type_hints = merge_variables(variables, options.on_conflict)?
+        .into_iter()
+        .map(|(var, merged)| (var, merged.type_hint))
+        .collect::<BTreeMap<_, _>>();
+
+    let requirements = requirements(variables, options.profiles)?;
+    let descriptions = descriptions(variables);
 
     let output = format!(
         r#"
@@ -48,11 +229,40 @@ declare namespace NodeJS {{
     }}
 }}
                "#,
-        vars.iter()
-            .map(|var| format!(
-                r#"
-         {}?: string"#,
-                var
+        type_hints
+            .iter()
+            .map(|(var, th)| format!(
+                r#"{}
+         {}{}: {}"#,
+                th.as_ref()
+                    .and_then(typescript_doc)
+                    .into_iter()
+                    .chain(match &requirements[var] {
+                        Requirement::InModes(modes) =>
+                            Some(format!("Only set in `{}`.", modes.join("`, `"))),
+                        _ => None,
+                    })
+                    .reduce(|doc, modes| format!("{doc} {modes}"))
+                    .into_iter()
+                    .chain(descriptions.get(var).map(|d| d.to_string()))
+                    // the description comes first, as what the variable is for
+                    .reduce(|doc, description| format!("{description}\n\n{doc}"))
+                    .map(|doc| format!("\n{}", jsdoc(&doc, "         ")))
+                    .unwrap_or_default(),
+                property_name(var),
+                match requirements[var] {
+                    Requirement::Required => "",
+                    _ => "?",
+                },
+                match (th.as_ref().and_then(typescript_type), inferred.get(*var)) {
+                    (Some(ts), _) => ts,
+                    // process.env only ever holds strings, so only unions of literals are
+                    // more precise than a string here
+                    (None, Some(th @ TypeHint::Union(_))) => {
+                        format!("{} /* inferred */", typescript_type(th).unwrap_or_default())
+                    }
+                    _ => "string".to_string(),
+                }
             ))
             .collect::<Vec<_>>()
             .join("")
@@ -63,19 +273,185 @@ declare namespace NodeJS {{
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
 
     use insta::assert_display_snapshot;
 
-    use crate::dotenv::generate_typescript_types;
+    use crate::dotenv::{
+        generate_typescript_types, generate_typescript_types_from_variables, load_variables,
+        merge::OnConflict, zod::Metadata, Options,
+    };
 
     #[test]
     fn introspect_typescript_types_gen() {
-        let output = generate_typescript_types(&[
-            PathBuf::from("src/dotenv/.env.test"),
-            PathBuf::from("src/dotenv/.env.test2"),
-        ])
+        let output = generate_typescript_types(
+            &[
+                PathBuf::from("src/dotenv/.env.test"),
+                PathBuf::from("src/dotenv/.env.test2"),
+            ],
+            &Options::default(),
+        )
         .unwrap();
         assert_display_snapshot!(output);
     }
+
+    #[test]
+    fn typescript_types_gen_with_profiles() {
+        let sources = [
+            (".env", "# @type port\nPORT=3000\n"),
+            (
+                ".env.production",
+                "# Where errors are reported.\nSENTRY_DSN=\n",
+            ),
+        ]
+        .map(|(path, source)| Metadata::new(source, Path::new(path)));
+        let options = Options {
+            profiles: true,
+            ..Default::default()
+        };
+
+        let variables = load_variables(sources.into_iter(), &options).unwrap();
+        let output = generate_typescript_types_from_variables(&variables, &options).unwrap();
+
+        assert!(output.contains("\n         PORT: string"));
+        assert!(output.contains(
+            "
+         /**
+          * Where errors are reported.
+          *
+          * Only set in `production`.
+          */
+         SENTRY_DSN?: string"
+        ));
+    }
+
+    #[test]
+    fn typescript_types_gen_with_type_hints() {
+        let sources = [
+            (
+                ".env.development",
+                "# @type 'dev' | 'qa'\nSTAGE=dev\n# @type 1 | 2 | false\nREPLICAS=1\n# @type port\nPORT=3000\n# @type 1.50 | 2\nRATIO=1.50\n",
+            ),
+            (".env.production", "# @type 'prod'\nSTAGE=prod\nREPLICAS=2\nPORT=80\n"),
+        ]
+        .map(|(path, source)| Metadata::new(source, Path::new(path)));
+
+        let variables = load_variables(sources.into_iter(), &Options::default()).unwrap();
+        let output =
+            |options: &Options| generate_typescript_types_from_variables(&variables, options);
+
+        let err = format!("{:#}", output(&Options::default()).unwrap_err());
+        assert!(
+            err.starts_with("found some conflicting types while parsing variables with type hints")
+        );
+
+        let output = output(&Options {
+            on_conflict: OnConflict::Union,
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert!(output.contains("\n         STAGE: 'dev' | 'qa' | 'prod'"));
+        assert!(output.contains("\n         REPLICAS: '1' | '2' | 'false'"));
+        // process.env holds the text as written
+        assert!(output.contains("\n         RATIO?: '1.50' | '2'"));
+        assert!(output.contains("\n         PORT: string"));
+    }
+
+    #[test]
+    fn typescript_types_gen_with_type_docs() {
+        let source = "\
+# @type number?
+TIMEOUT=
+# @type url
+API_URL=https://example.com
+# @type uuid
+TENANT_ID=0b5f8d2e-3c1a-4f6e-9a7b-2d4c6e8f0a1b
+# @type ('x' | 'y')[] sep=';'
+FEATURE_FLAGS=x;y
+# @type json<{ free: number; pro: number }>
+PLAN_LIMITS={\"free\": 10, \"pro\": 100}
+# @type duration(s)
+REQUEST_TIMEOUT=30s
+# @type datetime(iso)
+MAINTENANCE_WINDOW=2024-01-31T09:00:00Z
+# @type `redis://${string}`
+REDIS_URL=redis://localhost:6379
+# @type 'eu' | 'us'
+# @transform trim lowercase
+DEPLOY_REGION=eu
+# The number of requests handled at once.
+# @type int(1..)?
+# @default 16
+CONCURRENCY=
+";
+        let sources = [Metadata::new(source, Path::new(".env"))];
+
+        let variables = load_variables(sources.into_iter(), &Options::default()).unwrap();
+        let output =
+            generate_typescript_types_from_variables(&variables, &Options::default()).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn typescript_types_gen_with_descriptions() {
+        let sources = [
+            (
+                ".env",
+                "# The port to listen on.\n# @type port\nPORT=3000\n",
+            ),
+            (
+                ".env.local",
+                "PORT=8080\n# Where to find the API,\n# e.g. `http://*/api`.\nAPI_URL=\n",
+            ),
+        ]
+        .map(|(path, source)| Metadata::new(source, Path::new(path)));
+
+        let variables = load_variables(sources.into_iter(), &Options::default()).unwrap();
+        let output =
+            generate_typescript_types_from_variables(&variables, &Options::default()).unwrap();
+
+        assert!(output.contains("\n         /** The port to listen on. */\n         PORT: string"));
+
+        assert!(output.contains(
+            "
+         /**
+          * Where to find the API,
+          * e.g. `http://*\\/api`.
+          */
+         API_URL?: string"
+        ));
+    }
+
+    #[test]
+    fn typescript_types_gen_with_quoted_keys() {
+        let sources = [Metadata::new(
+            "APP.NAME=ntro\nmy-key=1\n",
+            Path::new(".env"),
+        )];
+        let variables = load_variables(sources.into_iter(), &Options::default()).unwrap();
+        let output =
+            generate_typescript_types_from_variables(&variables, &Options::default()).unwrap();
+
+        assert!(output.contains("\n         \"APP.NAME\": string"));
+        assert!(output.contains("\n         \"my-key\": string"));
+    }
+
+    #[test]
+    fn typescript_types_gen_denying_warnings() {
+        let sources = || {
+            [Metadata::new(
+                "# @type numbr\nPORT=3000\n",
+                Path::new(".env"),
+            )]
+        };
+        let options = Options {
+            deny_warnings: true,
+            ..Default::default()
+        };
+
+        assert!(load_variables(sources().into_iter(), &Options::default()).is_ok());
+        assert!(load_variables(sources().into_iter(), &options).is_err());
+    }
 }
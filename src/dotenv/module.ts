@@ -9,6 +9,8 @@ const serverEnvSchemas = {
 
 const processEnv = {};
 
+declare function readEnv(prop: string): string | undefined;
+
 /* --- MAIN IMPLEMENTATION BELOW --- */
 
 type ClientEnv = z.infer<z.ZodObject<typeof clientEnvSchemas>>;
@@ -52,7 +54,7 @@ function lookupEnv<T extends Record<string, ZodTypeAny>>(
   try {
     if (prop in parsers) {
       const parsed = parsers[prop as keyof typeof parsers]?.parse(
-        processEnv[prop as keyof typeof processEnv],
+        readEnv(prop),
         { path: [prop] }
       );
 
@@ -62,7 +64,13 @@ function lookupEnv<T extends Record<string, ZodTypeAny>>(
     }
     onNotFound();
   } catch (e) {
-    throw new BadEnvError(`failed to read ${prop} from proccess.env`, e);
+    const description = parsers[prop as keyof typeof parsers]?.description;
+    throw new BadEnvError(
+      [`failed to read ${prop} from proccess.env`, description]
+        .filter(Boolean)
+        .join(": "),
+      e
+    );
   }
 }
 
@@ -74,3 +82,66 @@ class BadEnvError extends Error {
     }
   }
 }
+
+/** Parses a duration, like `30s` or `5m`, into a number of `unit`s. */
+function duration(unit: string) {
+  return quantity(
+    "duration",
+    { ms: 1, s: 1000, m: 60_000, h: 3_600_000, d: 86_400_000 },
+    unit
+  );
+}
+
+/** Parses a byte size, like `512MB` or `1GiB`, into a number of `unit`s. */
+function bytes(unit: string) {
+  return quantity(
+    "byte size",
+    {
+      b: 1,
+      kb: 1e3,
+      mb: 1e6,
+      gb: 1e9,
+      tb: 1e12,
+      kib: 2 ** 10,
+      mib: 2 ** 20,
+      gib: 2 ** 30,
+      tib: 2 ** 40,
+    },
+    unit
+  );
+}
+
+/** The variable is only set in some modes, and may be left unset in the others. */
+function inModes<T extends ZodTypeAny>(modes: string[], schema: T) {
+  return modes.includes(process.env.NODE_ENV ?? "development")
+    ? schema
+    : schema.optional();
+}
+
+/** Decodes base64 data into UTF-8 text, throwing if it isn't either. */
+function decodeBase64(data: string) {
+  return new TextDecoder("utf-8", { fatal: true }).decode(
+    Uint8Array.from(atob(data), (c) => c.charCodeAt(0))
+  );
+}
+
+/** A plain number is taken to be in `unit` already. */
+function quantity(name: string, units: Record<string, number>, unit: string) {
+  return z.string().transform((value, ctx) => {
+    const [, amount, suffix] =
+      /^\s*(\d+(?:\.\d+)?)\s*([a-z]*)\s*$/i.exec(value) ?? [];
+    const factor = units[(suffix || unit).toLowerCase()];
+
+    if (amount === undefined || factor === undefined) {
+      ctx.addIssue({
+        code: z.ZodIssueCode.custom,
+        message: `Invalid ${name}, expected a number followed by one of ${Object.keys(
+          units
+        ).join(", ")}`,
+      });
+      return z.NEVER;
+    }
+
+    return (Number(amount) * factor) / units[unit.toLowerCase()]!;
+  });
+}
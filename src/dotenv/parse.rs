@@ -1,12 +1,23 @@
 use std::{
+    collections::BTreeMap,
     fs::File,
     io::{BufReader, Read},
+    ops::Range,
     path::PathBuf,
 };
 
 use anyhow::Context;
+use thiserror::Error;
 
-use super::typehint_parser::{ParseTyeHint, TypeHint};
+use super::{
+    config::CustomType,
+    diagnostic::{Diagnostic, Severity},
+    typehint_parser::{
+        parse_pattern, parse_transforms, parse_typedef, ParseTyeHint, TypeDefs, TypeHint,
+        TypeHintError,
+    },
+    zod::Metadata,
+};
 
 type WithLineNumber<T> = (T, usize);
 
@@ -14,6 +25,43 @@ type WithLineNumber<T> = (T, usize);
 pub struct Variable {
     pub type_hint: Option<WithLineNumber<TypeHint>>,
     pub key: String,
+    /// The value with quotes removed and escapes processed.
+    pub value: String,
+    /// Byte range of the whole assignment (e.g. `export KEY="value"`) in the source.
+    pub span: Range<usize>,
+    /// Line (zero based) on which the assignment starts.
+    pub line: usize,
+    /// The free text of the comments documenting the variable.
+    pub description: Option<String>,
+    /// The `@tag` annotations in the comments documenting the variable.
+    pub annotations: Vec<Annotation>,
+}
+
+/// An `@tag` in a comment, which runs until the end of its line, e.g. `@type 'a' | 'b'`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    /// The annotation as written, starting at the `@`.
+    pub text: String,
+    pub line: usize,
+    /// Byte offset of the `@` into the line.
+    pub column: usize,
+}
+
+impl Annotation {
+    /// The name of the tag, without the `@`.
+    pub fn tag(&self) -> &str {
+        let end = self.text[1..]
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '-' && c != '_')
+            .map(|i| i + 1)
+            .unwrap_or(self.text.len());
+
+        &self.text[1..end]
+    }
+
+    /// Everything after the tag.
+    pub fn value(&self) -> &str {
+        self.text[1 + self.tag().len()..].trim()
+    }
 }
 
 impl Variable {
@@ -22,68 +70,623 @@ impl Variable {
     }
 }
 
-pub fn parse_variables(source: &str) -> Vec<&str> {
-    return source
-        .lines()
-        .filter_map(|line| {
-            if line.starts_with('#') {
-                return None;
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("{kind} on line {}, column {}", .line + 1, .column + 1)]
+pub struct SyntaxError {
+    pub kind: SyntaxErrorKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SyntaxErrorKind {
+    #[error("expected a variable name but found {0:?}")]
+    ExpectedKey(char),
+    #[error("expected `=` after {0:?}")]
+    ExpectedEquals(String),
+    #[error("unterminated {0} quoted value")]
+    UnterminatedQuote(char),
+    #[error("unexpected {0:?} after the closing quote")]
+    TrailingCharacters(char),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Comment<'source> {
+    text: &'source str,
+    line: usize,
+    column: usize,
+}
+
+#[derive(Debug)]
+enum Entry<'source> {
+    Comment(Comment<'source>),
+    /// A variable, along with the comment trailing it on the same line.
+    Assignment(Variable, Option<Comment<'source>>),
+}
+
+/// Parses .env sources following the rules shared by the common dotenv implementations:
+///
+/// - an optional `export` prefix before the key
+/// - unquoted values run up to the end of the line or an inline comment (` #`), and are trimmed
+/// - single, double and backtick quoted values may span several lines, and escape their
+///   own quote with `\`; double quoted values also expand `\n`, `\r`, `\t` and `\\`
+/// - CRLF line endings and a leading UTF-8 BOM are accepted
+struct Parser<'source> {
+    source: &'source str,
+    position: usize,
+    line: usize,
+    line_start: usize,
+}
+
+impl<'source> Parser<'source> {
+    fn new(source: &'source str) -> Self {
+        let position = if source.starts_with('\u{feff}') {
+            '\u{feff}'.len_utf8()
+        } else {
+            0
+        };
+
+        Self {
+            source,
+            position,
+            line: 0,
+            line_start: position,
+        }
+    }
+
+    fn byte(&self) -> Option<u8> {
+        self.source.as_bytes().get(self.position).copied()
+    }
+
+    fn peek_byte(&self) -> Option<u8> {
+        self.source.as_bytes().get(self.position + 1).copied()
+    }
+
+    fn char(&self) -> Option<char> {
+        self.source[self.position..].chars().next()
+    }
+
+    fn step(&mut self) {
+        if let Some(ch) = self.char() {
+            if ch == '\n' {
+                self.line += 1;
+                self.line_start = self.position + 1;
             }
-            return match line.split('=').collect::<Vec<_>>()[..] {
-                [ident, ..] if !ident.is_empty() => Some(ident.trim()),
-                _ => None,
-            };
-        })
-        .collect::<Vec<_>>();
+            self.position += ch.len_utf8();
+        }
+    }
+
+    fn is_at_line_end(&self) -> bool {
+        matches!(self.byte(), None | Some(b'\n' | b'\r'))
+    }
+
+    fn skip_blanks(&mut self) {
+        while let Some(b' ' | b'\t') = self.byte() {
+            self.step();
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\r' | b'\n') = self.byte() {
+            self.step();
+        }
+    }
+
+    fn skip_to_line_end(&mut self) {
+        while !self.is_at_line_end() {
+            self.step();
+        }
+    }
+
+    fn error(&self, kind: SyntaxErrorKind) -> SyntaxError {
+        SyntaxError {
+            kind,
+            line: self.line,
+            column: self.position - self.line_start,
+        }
+    }
+
+    fn parse(mut self) -> Result<Vec<Entry<'source>>, SyntaxError> {
+        let mut entries = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+
+            match self.byte() {
+                None => break,
+                Some(b'#') => entries.push(Entry::Comment(self.comment())),
+                Some(_) => {
+                    let (var, trailing_comment) = self.assignment()?;
+                    entries.push(Entry::Assignment(var, trailing_comment))
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn comment(&mut self) -> Comment<'source> {
+        let start = self.position;
+        let line = self.line;
+        let column = self.position - self.line_start;
+
+        self.skip_to_line_end();
+
+        Comment {
+            text: &self.source[start..self.position],
+            line,
+            column,
+        }
+    }
+
+    fn assignment(&mut self) -> Result<(Variable, Option<Comment<'source>>), SyntaxError> {
+        let start = self.position;
+        let line = self.line;
+
+        let rest = &self.source[self.position..];
+        if rest.starts_with("export")
+            && matches!(rest.as_bytes().get("export".len()), Some(b' ' | b'\t'))
+        {
+            self.position += "export".len();
+            self.skip_blanks();
+        }
+
+        let key = self.key()?;
+
+        self.skip_blanks();
+
+        match self.byte() {
+            Some(b'=') => self.step(),
+            Some(b':') if matches!(self.peek_byte(), Some(b' ' | b'\t')) => self.step(),
+            _ => return Err(self.error(SyntaxErrorKind::ExpectedEquals(key))),
+        }
+
+        self.skip_blanks();
+
+        let value = match self.byte() {
+            Some(quote @ (b'\'' | b'"' | b'`')) => self.quoted_value(quote)?,
+            _ => self.unquoted_value(),
+        };
+
+        let end = self.position;
+
+        self.skip_blanks();
+
+        let trailing_comment = match self.byte() {
+            Some(b'#') => Some(self.comment()),
+            _ if self.is_at_line_end() => None,
+            _ => {
+                let found = self.char().expect("should not be at the end of the source");
+                return Err(self.error(SyntaxErrorKind::TrailingCharacters(found)));
+            }
+        };
+
+        let var = Variable {
+            type_hint: None,
+            key,
+            value,
+            span: start..end,
+            line,
+            description: None,
+            annotations: Vec::new(),
+        };
+
+        Ok((var, trailing_comment))
+    }
+
+    fn key(&mut self) -> Result<String, SyntaxError> {
+        let start = self.position;
+
+        while let Some(b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_' | b'.' | b'-') = self.byte() {
+            self.step();
+        }
+
+        if start == self.position {
+            let found = self.char().unwrap_or('\n');
+            return Err(self.error(SyntaxErrorKind::ExpectedKey(found)));
+        }
+
+        Ok(self.source[start..self.position].to_string())
+    }
+
+    fn unquoted_value(&mut self) -> String {
+        let start = self.position;
+        let mut end = self.position;
+
+        while !self.is_at_line_end() {
+            match self.byte() {
+                Some(b'#') if end < self.position || start == self.position => break,
+                Some(b' ' | b'\t') => {}
+                _ => end = self.position + self.char().map_or(1, char::len_utf8),
+            }
+            self.step();
+        }
+
+        // only the trimmed value is part of the assignment
+        self.position = end;
+
+        self.source[start..end].to_string()
+    }
+
+    fn quoted_value(&mut self, quote: u8) -> Result<String, SyntaxError> {
+        let unterminated = self.error(SyntaxErrorKind::UnterminatedQuote(quote as char));
+
+        self.step();
+
+        let mut value = String::new();
+
+        loop {
+            match (self.byte(), self.peek_byte()) {
+                (None, _) => return Err(unterminated),
+                (Some(c), _) if c == quote => {
+                    self.step();
+                    break;
+                }
+                (Some(b'\\'), Some(next)) => {
+                    let escaped = match next {
+                        c if c == quote => Some(quote as char),
+                        b'n' if quote == b'"' => Some('\n'),
+                        b'r' if quote == b'"' => Some('\r'),
+                        b't' if quote == b'"' => Some('\t'),
+                        b'\\' if quote == b'"' => Some('\\'),
+                        _ => None,
+                    };
+
+                    match escaped {
+                        Some(ch) => {
+                            value.push(ch);
+                            self.step();
+                            self.step();
+                        }
+                        None => {
+                            value.push('\\');
+                            self.step();
+                        }
+                    }
+                }
+                (Some(b'\r'), Some(b'\n')) => self.step(),
+                (Some(_), _) => {
+                    value.push(self.char().expect("should not be at the end of the source"));
+                    self.step();
+                }
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+/// The text of a comment without its pounds, and the `@tag` annotation it is, if it is one.
+fn annotation<'c>(comment: &Comment<'c>) -> (&'c str, Option<Annotation>) {
+    let after_pounds = comment.text.trim_start_matches('#');
+    let text = after_pounds.trim();
+    let offset = comment.text.len() - after_pounds.trim_start().len();
+
+    let is_annotation =
+        text.starts_with('@') && text[1..].starts_with(|c: char| c.is_ascii_alphabetic());
+
+    let annotation = is_annotation.then(|| Annotation {
+        text: text.to_string(),
+        line: comment.line,
+        column: comment.column + offset,
+    });
+
+    (text, annotation)
+}
+
+/// Attaches the block of comment lines directly above a variable, and the comment trailing it,
+/// as its documentation; `@tag` lines become annotations and everything else the description.
+fn document(var: &mut Variable, comments: &[Comment]) {
+    let mut description = Vec::new();
+
+    for comment in comments {
+        match annotation(comment) {
+            (_, Some(annotation)) => var.annotations.push(annotation),
+            (text, None) => description.push(text),
+        }
+    }
+
+    let description = description.join("\n").trim().to_string();
+
+    if !description.is_empty() {
+        var.description = Some(description);
+    }
 }
 
-pub fn parse_variables_with_type_hints(source: &str) -> Vec<Variable> {
-    enum Token<'source> {
-        LineComment(&'source str, usize),
-        Ident(&'source str, usize),
+/// The description of each variable, from the first file that documents it.
+pub fn descriptions(variables: &[(Variable, Metadata)]) -> BTreeMap<&str, &str> {
+    let mut descriptions = BTreeMap::new();
+
+    for (var, _) in variables {
+        if let Some(description) = &var.description {
+            descriptions
+                .entry(var.key.as_str())
+                .or_insert(description.as_str());
+        }
     }
 
-    let mut tokens = source
-        .lines()
-        .enumerate()
-        .filter_map(|(l_num, line)| {
-            if line.starts_with('#') {
-                return Some(Token::LineComment(line, l_num));
+    descriptions
+}
+
+pub fn parse_variables(source: &str) -> Result<Vec<Variable>, SyntaxError> {
+    let mut vars = Vec::new();
+    let mut block: Vec<Comment> = Vec::new();
+
+    for entry in Parser::new(source).parse()? {
+        match entry {
+            Entry::Comment(comment) => {
+                if block
+                    .last()
+                    .is_some_and(|last| last.line + 1 != comment.line)
+                {
+                    block.clear();
+                }
+                block.push(comment);
             }
-            return match line.split('=').collect::<Vec<_>>()[..] {
-                [ident, ..] if !ident.is_empty() => Some(Token::Ident(ident.trim(), l_num)),
-                _ => None,
-            };
+            Entry::Assignment(mut var, trailing_comment) => {
+                if block.last().is_some_and(|last| last.line + 1 != var.line) {
+                    block.clear();
+                }
+                block.extend(trailing_comment);
+
+                document(&mut var, &block);
+                block.clear();
+
+                vars.push(var);
+            }
+        }
+    }
+
+    Ok(vars)
+}
+
+fn annotation_warning(
+    meta: &Metadata,
+    annotation: &Annotation,
+    message: String,
+    error: Option<TypeHintError>,
+) -> Diagnostic {
+    let (message, column, width) = match error {
+        Some(e) => (
+            format!("{message}: {}", e.message),
+            annotation.column + e.position,
+            e.width,
+        ),
+        None => (message, annotation.column, annotation.text.len()),
+    };
+
+    Diagnostic {
+        severity: Severity::Warning,
+        message,
+        meta: meta.clone(),
+        line: annotation.line,
+        column,
+        width,
+    }
+}
+
+/// Parses the `@typedef Name <type>` declarations of every source, on top of the custom types.
+pub fn parse_typedefs(
+    sources: &[Metadata],
+    custom_types: &BTreeMap<String, CustomType>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> anyhow::Result<TypeDefs> {
+    let mut typedefs = custom_types
+        .iter()
+        .map(|(name, ty)| {
+            let name: Box<str> = name.as_str().into();
+            (name.clone(), TypeHint::Custom(name, Box::new(ty.clone())))
         })
-        .peekable();
+        .collect::<TypeDefs>();
+    let mut declarations: BTreeMap<Box<str>, (&Metadata, Annotation)> = BTreeMap::new();
 
-    let mut vars = Vec::new();
+    for meta in sources {
+        let entries = Parser::new(&meta.source)
+            .parse()
+            .with_context(|| format!("failed to parse {:?}", meta.path))?;
 
-    loop {
-        match tokens.next() {
-            None => break,
-            Some(token) => match (token, tokens.peek()) {
-                (Token::LineComment(comment, l_num), Some(Token::Ident(ident, _))) => {
-                    let var = Variable {
-                        type_hint: comment.into_type_hint().map(|th| (th, l_num)),
-                        key: ident.to_string(),
-                    };
-                    vars.push(var);
-                    tokens.next();
+        let comments = entries.iter().flat_map(|entry| match entry {
+            Entry::Comment(comment) => Some(comment),
+            Entry::Assignment(_, comment) => comment.as_ref(),
+        });
+
+        for annotation in comments.filter_map(|comment| annotation(comment).1) {
+            if annotation.tag() != "typedef" {
+                continue;
+            }
+
+            let (name, th) = match parse_typedef(&annotation.text, &typedefs) {
+                Ok(typedef) => typedef,
+                Err(e) => {
+                    diagnostics.push(annotation_warning(
+                        meta,
+                        &annotation,
+                        "invalid type definition".to_string(),
+                        Some(e),
+                    ));
+                    continue;
                 }
-                (Token::Ident(ident, _), _) => {
-                    vars.push(Variable {
-                        type_hint: None,
-                        key: ident.to_string(),
+            };
+
+            match (typedefs.get(&name), declarations.get(&name)) {
+                (Some(existing), Some((other_meta, other))) if existing != &th => {
+                    diagnostics.push(Diagnostic {
+                        severity: Severity::Error,
+                        ..annotation_warning(
+                            meta,
+                            &annotation,
+                            format!(
+                                "the type `{name}` is already defined as `{existing}`, in {:?} on line {}",
+                                other_meta.path,
+                                other.line + 1
+                            ),
+                            None,
+                        )
                     });
                 }
+                (Some(_), None) => diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    ..annotation_warning(
+                        meta,
+                        &annotation,
+                        format!("the type `{name}` is already defined in the config"),
+                        None,
+                    )
+                }),
+                (Some(_), Some(_)) => {}
+                (None, _) => {
+                    typedefs.insert(name.clone(), th);
+                    declarations.insert(name, (meta, annotation));
+                }
+            }
+        }
+    }
 
-                _ => {}
-            },
-        };
+    Ok(typedefs)
+}
+
+/// The value of a `@default` annotation, without the quotes around it, if any.
+fn default_value(annotation: &Annotation) -> &str {
+    let value = annotation.value();
+
+    match value.as_bytes() {
+        [quote @ (b'\'' | b'"'), .., last] if quote == last => &value[1..value.len() - 1],
+        _ => value,
+    }
+}
+
+/// Parses the variables along with their annotations, reporting malformed ones as warnings.
+pub fn parse_variables_with_type_hints(
+    meta: &Metadata,
+    typedefs: &TypeDefs,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<Variable>, SyntaxError> {
+    let mut vars = parse_variables(&meta.source)?;
+
+    for var in &mut vars {
+        let annotation = |tag: &str| var.annotations.iter().find(|a| a.tag() == tag);
+
+        if let Some(annotation) = annotation("type") {
+            match (annotation.text.as_str(), typedefs).into_type_hint() {
+                Ok(th) => var.type_hint = Some((th, annotation.line)),
+                Err(e) => diagnostics.push(annotation_warning(
+                    meta,
+                    annotation,
+                    format!("invalid type hint for `{}`", var.key),
+                    Some(e),
+                )),
+            }
+        }
+
+        if let Some(annotation) = annotation("pattern") {
+            match parse_pattern(&annotation.text) {
+                Ok(pattern) => {
+                    let (th, line) = var
+                        .type_hint
+                        .take()
+                        .unwrap_or((TypeHint::String, annotation.line));
+
+                    let (th, optional) = match th {
+                        TypeHint::Optional(th) => (*th, true),
+                        th => (th, false),
+                    };
+
+                    let th = match th {
+                        TypeHint::String | TypeHint::Format(_) => {
+                            TypeHint::Matching(Box::new(th), pattern)
+                        }
+                        th => {
+                            diagnostics.push(annotation_warning(
+                                meta,
+                                annotation,
+                                format!(
+                                    "the pattern for `{}` is ignored, it only applies to strings, not `{th}`",
+                                    var.key
+                                ),
+                                None,
+                            ));
+                            th
+                        }
+                    };
+
+                    var.type_hint = Some((if optional { th.optional() } else { th }, line));
+                }
+                Err(e) => diagnostics.push(annotation_warning(
+                    meta,
+                    annotation,
+                    format!("invalid pattern for `{}`", var.key),
+                    Some(e),
+                )),
+            }
+        }
+
+        if let Some(annotation) = annotation("transform") {
+            match parse_transforms(&annotation.text) {
+                Ok(transforms) => {
+                    let (th, line) = var
+                        .type_hint
+                        .take()
+                        .unwrap_or((TypeHint::String, annotation.line));
+
+                    let th = match th {
+                        TypeHint::Optional(th) => TypeHint::Transformed(th, transforms).optional(),
+                        th => TypeHint::Transformed(Box::new(th), transforms),
+                    };
+
+                    var.type_hint = Some((th, line));
+                }
+                Err(e) => diagnostics.push(annotation_warning(
+                    meta,
+                    annotation,
+                    format!("invalid transforms for `{}`", var.key),
+                    Some(e),
+                )),
+            }
+        }
+
+        if let Some(annotation) = annotation("optional") {
+            var.type_hint = match var.type_hint.take() {
+                Some((th, line)) => Some((th.optional(), line)),
+                None => Some((TypeHint::String.optional(), annotation.line)),
+            };
+        }
+
+        // `@required` wins over a type hint that makes the variable optional
+        if annotation("required").is_some() {
+            var.type_hint = var.type_hint.take().map(|(th, line)| match th {
+                TypeHint::Optional(th) => (*th, line),
+                th => (th, line),
+            });
+        }
+
+        if let Some(annotation) = annotation("default") {
+            if annotation.value().is_empty() {
+                diagnostics.push(annotation_warning(
+                    meta,
+                    annotation,
+                    format!("the default for `{}` is missing a value", var.key),
+                    None,
+                ));
+            } else {
+                let (th, line) = var
+                    .type_hint
+                    .take()
+                    .unwrap_or((TypeHint::String, annotation.line));
+
+                let th = match th {
+                    TypeHint::Optional(th) => *th,
+                    th => th,
+                };
+
+                var.type_hint = Some((
+                    TypeHint::Defaulted(Box::new(th), default_value(annotation).into()),
+                    line,
+                ));
+            }
+        }
     }
 
-    vars
+    Ok(vars)
 }
 
 pub fn get_texts(files: &[PathBuf]) -> Vec<(String, &PathBuf)> {
@@ -110,11 +713,35 @@ pub fn get_texts(files: &[PathBuf]) -> Vec<(String, &PathBuf)> {
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+    };
 
     use insta::assert_debug_snapshot;
+    use proptest::prelude::*;
+
+    use crate::dotenv::{
+        diagnostic::{Diagnostic, Severity},
+        parse::{
+            get_texts, parse_typedefs, parse_variables, parse_variables_with_type_hints,
+            SyntaxError, SyntaxErrorKind, Variable,
+        },
+        typehint_parser::{TypeDefs, TypeHint},
+        zod::Metadata,
+    };
+
+    fn with_type_hints(source: &str) -> (Vec<Variable>, Vec<Diagnostic>) {
+        let meta = Metadata {
+            source: source.into(),
+            path: PathBuf::from(".env").into(),
+        };
+        let mut diagnostics = vec![];
+        let vars =
+            parse_variables_with_type_hints(&meta, &TypeDefs::new(), &mut diagnostics).unwrap();
 
-    use crate::dotenv::parse::{get_texts, parse_variables_with_type_hints};
+        (vars, diagnostics)
+    }
 
     #[test]
     fn parsing_variables_with_type_hints() {
@@ -126,7 +753,8 @@ mod tests {
         let output = get_texts(&sources);
 
         for (content, file) in output {
-            let vars = parse_variables_with_type_hints(&content);
+            let (vars, diagnostics) = with_type_hints(&content);
+            assert!(diagnostics.is_empty());
             insta::with_settings!({
                 description => file.to_string_lossy()
             }, {
@@ -134,4 +762,427 @@ mod tests {
             })
         }
     }
+
+    #[test]
+    fn parsing_dotenv_syntax() {
+        let source = "\u{feff}# comment\r\n\
+            export EXPORTED=yes\r\n\
+            UNQUOTED = some value   # a comment\n\
+            HASH=abc#def\n\
+            EMPTY=\n\
+            EMPTY_WITH_COMMENT= # nothing\n\
+            SINGLE='it\\'s # not a comment'\n\
+            DOUBLE=\"line\\nbreak \\\"quoted\\\" \\\\ \\$HOME\"\n\
+            BACKTICK=`has 'single' and \"double\"`\n\
+            MULTILINE=\"first\r\nsecond\"\n\
+            YAML_STYLE: value\n\
+            UNICODE=’qa’\n";
+
+        let vars = parse_variables(source).unwrap();
+
+        assert_eq!(
+            vars.iter()
+                .map(|v| (v.key.as_str(), v.value.as_str(), v.line))
+                .collect::<Vec<_>>(),
+            vec![
+                ("EXPORTED", "yes", 1),
+                ("UNQUOTED", "some value", 2),
+                ("HASH", "abc#def", 3),
+                ("EMPTY", "", 4),
+                ("EMPTY_WITH_COMMENT", "", 5),
+                ("SINGLE", "it's # not a comment", 6),
+                ("DOUBLE", "line\nbreak \"quoted\" \\ \\$HOME", 7),
+                ("BACKTICK", "has 'single' and \"double\"", 8),
+                ("MULTILINE", "first\nsecond", 9),
+                ("YAML_STYLE", "value", 11),
+                ("UNICODE", "’qa’", 12),
+            ]
+        );
+
+        assert_eq!(&source[vars[0].span.clone()], "export EXPORTED=yes");
+        assert_eq!(&source[vars[1].span.clone()], "UNQUOTED = some value");
+        assert_eq!(
+            &source[vars[8].span.clone()],
+            "MULTILINE=\"first\r\nsecond\""
+        );
+    }
+
+    #[test]
+    fn parsing_doc_comments() {
+        let source = "# detached comment
+
+# The port to listen on.
+#
+# @type number
+# Defaults to 3000 in development.
+#   @deprecated use LISTEN_ADDRESS
+PORT=3000
+NO_DOCS=x
+TRAILING=1 # @type number
+";
+
+        let (vars, _) = with_type_hints(source);
+
+        assert_eq!(vars[0].key, "PORT");
+        assert_eq!(
+            vars[0].description.as_deref(),
+            Some("The port to listen on.\n\nDefaults to 3000 in development.")
+        );
+        assert_eq!(
+            vars[0]
+                .annotations
+                .iter()
+                .map(|a| (a.tag(), a.value(), a.line, a.column))
+                .collect::<Vec<_>>(),
+            vec![
+                ("type", "number", 4, 2),
+                ("deprecated", "use LISTEN_ADDRESS", 6, 4)
+            ]
+        );
+        assert_eq!(vars[0].type_hint, Some((TypeHint::NUMBER, 4)));
+
+        assert_eq!(vars[1].key, "NO_DOCS");
+        assert_eq!(vars[1].description, None);
+        assert!(vars[1].annotations.is_empty());
+
+        assert_eq!(vars[2].key, "TRAILING");
+        assert_eq!(vars[2].annotations[0].column, 13);
+        assert_eq!(vars[2].type_hint, Some((TypeHint::NUMBER, 9)));
+    }
+
+    #[test]
+    fn reporting_invalid_type_hints() {
+        let source = "# @type nubmer
+PORT=3000
+STAGE=dev # @type 'dev' | 'qa
+";
+
+        let (vars, diagnostics) = with_type_hints(source);
+
+        assert_eq!(vars[0].type_hint, None);
+        assert_eq!(vars[1].type_hint, None);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| (d.severity, d.line, d.column, d.width))
+                .collect::<Vec<_>>(),
+            vec![(Severity::Warning, 0, 8, 6), (Severity::Warning, 2, 26, 3)]
+        );
+        assert_eq!(
+            diagnostics[1].message,
+            "invalid type hint for `STAGE`: unterminated string literal `'qa`"
+        );
+    }
+
+    #[test]
+    fn parsing_typedefs() {
+        let sources = [
+            "# @typedef Stage 'qa' | 'staging' | 'production'
+
+# @type Stage
+STAGE=qa
+# @typedef Stages Stage[]
+# @type Stages?
+STAGES=
+",
+            "# @typedef Stage 'qa' | 'staging' | 'production'
+# @typedef Stage 'qa' | 'production'
+# @typedef Port numbr
+APP_STAGE=qa # @type Stag
+",
+        ]
+        .map(|source| Metadata::new(source, Path::new(".env")));
+
+        let mut diagnostics = vec![];
+        let typedefs = parse_typedefs(&sources, &BTreeMap::new(), &mut diagnostics).unwrap();
+
+        assert_eq!(
+            typedefs.keys().map(AsRef::as_ref).collect::<Vec<_>>(),
+            ["Stage", "Stages"]
+        );
+
+        let vars = sources
+            .iter()
+            .flat_map(|meta| {
+                parse_variables_with_type_hints(meta, &typedefs, &mut diagnostics).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let stage = &typedefs["Stage"];
+        assert_eq!(
+            vars[0].type_hint,
+            Some((TypeHint::Named("Stage".into(), Box::new(stage.clone())), 2))
+        );
+        assert_eq!(
+            vars[1].type_hint.as_ref().map(|(th, _)| th.to_string()),
+            Some("Stages?".to_string())
+        );
+        assert_eq!(vars[2].type_hint, None);
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| (d.severity, d.message.as_str(), d.line, d.column, d.width))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    Severity::Error,
+                    "the type `Stage` is already defined as `'qa' | 'staging' | 'production'`, in \".env\" on line 1",
+                    1,
+                    2,
+                    34
+                ),
+                (
+                    Severity::Warning,
+                    "invalid type definition: unknown type `numbr`, expected one of `string`, `number`, `int`, `port`, `boolean`, `url`, `email`, `uuid`, `ip`, `hostname`, `base64`, `date`, `datetime`, `duration`, `bytes`, `json` or a union of 'literals', did you mean `number`?",
+                    2,
+                    16,
+                    5
+                ),
+                (
+                    Severity::Warning,
+                    "invalid type hint for `APP_STAGE`: unknown type `Stag`, expected one of `string`, `number`, `int`, `port`, `boolean`, `url`, `email`, `uuid`, `ip`, `hostname`, `base64`, `date`, `datetime`, `duration`, `bytes`, `json` or a union of 'literals', did you mean `Stage`?",
+                    3,
+                    21,
+                    4
+                ),
+            ]
+        );
+
+        let sources = [Metadata::new(
+            "# @typedef Env 'qa' | 'prod'\n",
+            Path::new(".env"),
+        )];
+        let mut diagnostics = vec![];
+        let typedefs = parse_typedefs(&sources, &BTreeMap::new(), &mut diagnostics).unwrap();
+
+        assert!(typedefs.is_empty());
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| (d.message.as_str(), d.line, d.column, d.width))
+                .collect::<Vec<_>>(),
+            vec![(
+                "invalid type definition: the name `Env` is already declared in the generated module",
+                0,
+                11,
+                3
+            )]
+        );
+    }
+
+    #[test]
+    fn parsing_patterns() {
+        let source = "# @pattern /^[a-z]+$/i
+REGION=eu
+# @type url?
+# @pattern /^https:/
+API_URL=
+# @type number
+# @pattern /^1/
+NUMBER=1
+# @pattern /[a-/
+INVALID=
+";
+
+        let (vars, diagnostics) = with_type_hints(source);
+
+        assert_eq!(
+            vars.iter()
+                .map(|var| var.type_hint.as_ref().map(|(th, _)| th.to_string()))
+                .collect::<Vec<_>>(),
+            vec![
+                Some("string /^[a-z]+$/i".to_string()),
+                Some("url /^https:/?".to_string()),
+                Some("number".to_string()),
+                None
+            ]
+        );
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| (d.message.as_str(), d.line, d.column, d.width))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "the pattern for `NUMBER` is ignored, it only applies to strings, not `number`",
+                    6,
+                    2,
+                    13
+                ),
+                (
+                    "invalid pattern for `INVALID`: invalid regex: unclosed character class",
+                    8,
+                    12,
+                    3
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn parsing_transforms() {
+        let source = "# @transform trim lowercase
+REGION=eu
+# @type int?
+# @transform split(',')
+PORTS=
+# @pattern /^[a-z]+$/
+# @transform base64
+SECRET=
+# @transform trim reverse
+INVALID=
+";
+
+        let (vars, diagnostics) = with_type_hints(source);
+
+        assert_eq!(
+            vars.iter()
+                .map(|var| var.type_hint.as_ref().map(|(th, _)| th.to_string()))
+                .collect::<Vec<_>>(),
+            vec![
+                Some("string @transform trim lowercase".to_string()),
+                Some("int @transform split(',')?".to_string()),
+                Some("string /^[a-z]+$/ @transform base64".to_string()),
+                None
+            ]
+        );
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| (d.message.as_str(), d.line, d.column, d.width))
+                .collect::<Vec<_>>(),
+            vec![(
+                "invalid transforms for `INVALID`: unknown transform `reverse`, expected any of `trim`, `lowercase`, `uppercase`, `base64` or `split(',')`",
+                8,
+                18,
+                7
+            )]
+        );
+    }
+
+    #[test]
+    fn parsing_defaults() {
+        let source = "# @type port?
+# @default 3000
+PORT=
+# @default 'info level'
+LOG_LEVEL=
+# @optional
+# @default \"\"
+PREFIX=
+# @type int
+# @default
+INVALID=
+";
+
+        let (vars, diagnostics) = with_type_hints(source);
+
+        assert_eq!(
+            vars.iter()
+                .map(|var| var.type_hint.as_ref().map(|(th, _)| th.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                Some(TypeHint::Defaulted(
+                    Box::new(TypeHint::Number {
+                        integer: true,
+                        min: Some(1.0),
+                        max: Some(65535.0)
+                    }),
+                    "3000".into()
+                )),
+                Some(TypeHint::Defaulted(
+                    Box::new(TypeHint::String),
+                    "info level".into()
+                )),
+                Some(TypeHint::Defaulted(Box::new(TypeHint::String), "".into())),
+                Some(TypeHint::Number {
+                    integer: true,
+                    min: None,
+                    max: None
+                }),
+            ]
+        );
+        assert!(!vars[0].type_hint.as_ref().unwrap().0.is_optional());
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| (d.message.as_str(), d.line, d.column, d.width))
+                .collect::<Vec<_>>(),
+            vec![("the default for `INVALID` is missing a value", 9, 2, 8)]
+        );
+    }
+
+    #[test]
+    fn parsing_required_annotations() {
+        let (vars, _) =
+            with_type_hints("# @type url?\n# @required\nAPI_URL=\n# @required\nNAME=\n");
+
+        assert_eq!(
+            vars.iter()
+                .map(|var| var.type_hint.as_ref().map(|(th, _)| th.to_string()))
+                .collect::<Vec<_>>(),
+            vec![Some("url".to_string()), None]
+        );
+    }
+
+    #[test]
+    fn parsing_invalid_dotenv_syntax() {
+        let err = |source: &str| parse_variables(source).unwrap_err();
+
+        assert_eq!(
+            err("NAME=\"value\"\nNEXT_PUBLIC_KEY=\"yoa\",\n"),
+            SyntaxError {
+                kind: SyntaxErrorKind::TrailingCharacters(','),
+                line: 1,
+                column: 21,
+            }
+        );
+        assert_eq!(
+            err("KEY=\n\nOTHER='unterminated\n"),
+            SyntaxError {
+                kind: SyntaxErrorKind::UnterminatedQuote('\''),
+                line: 2,
+                column: 6,
+            }
+        );
+        assert_eq!(
+            err("JUST_A_KEY\n"),
+            SyntaxError {
+                kind: SyntaxErrorKind::ExpectedEquals("JUST_A_KEY".to_string()),
+                line: 0,
+                column: 10,
+            }
+        );
+        assert_eq!(
+            err("=value"),
+            SyntaxError {
+                kind: SyntaxErrorKind::ExpectedKey('='),
+                line: 0,
+                column: 0,
+            }
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn parsing_never_panics(source in r#"([A-Za-z_ =:#@'"`\\\r\n]|export|type|\PC)*"#) {
+            if let Ok(vars) = parse_variables(&source) {
+                for var in vars {
+                    prop_assert!(source.get(var.span.clone()).is_some());
+                }
+            }
+        }
+
+        #[test]
+        fn reporting_type_hints_never_panics(source in r#"(# @type ([a-z']|\PC){0,8}\n[A-Z]=[a-z0-9 ]{0,4}\n){0,4}"#) {
+            let (_, diagnostics) = with_type_hints(&source);
+
+            for d in diagnostics {
+                let line = source.lines().nth(d.line).unwrap_or_default();
+                prop_assert!(line.get(d.column..d.column + d.width).is_some());
+                let _ = d.to_string();
+            }
+        }
+    }
 }
@@ -0,0 +1,262 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{bail, Result};
+
+use super::{parse::Variable, requirement::is_example, zod::Metadata};
+
+/// An assignment of a variable, in one of the files.
+type Entry<'v> = &'v (Variable, Metadata);
+
+/// The modes every project has, even without a `.env.<mode>` file for them.
+pub const STANDARD_MODES: [&str; 3] = ["development", "production", "test"];
+
+/// Where a file sits in the layering of dotenv-flow and Next.js, from its name, e.g.
+/// `.env.production.local`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layer {
+    /// The mode the file is specific to, if any.
+    pub mode: Option<String>,
+    /// Whether the file holds local overrides, which aren't meant to be committed.
+    pub local: bool,
+}
+
+impl Layer {
+    /// The layer of `.env`, `.env.local`, `.env.<mode>` or `.env.<mode>.local`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        let rest = name.strip_prefix(".env")?;
+
+        let (mode, local) = match rest.strip_suffix(".local").unwrap_or(rest) {
+            "" => (None, rest == ".local"),
+            mode => (Some(mode.strip_prefix('.')?), rest.ends_with(".local")),
+        };
+
+        match mode {
+            Some(mode) if mode.is_empty() || mode.contains('.') => None,
+            mode => Some(Self {
+                mode: mode.map(str::to_string),
+                local,
+            }),
+        }
+    }
+
+    /// Whether the file is loaded in the mode. `.env.local` isn't loaded in the `test` mode, so
+    /// that tests are the same for everyone.
+    pub fn applies_to(&self, mode: &str) -> bool {
+        match &self.mode {
+            Some(m) => m == mode,
+            None => !(self.local && mode == "test"),
+        }
+    }
+
+    /// The higher the precedence, the more the layer overrides the others.
+    fn precedence(&self) -> u8 {
+        match (&self.mode, self.local) {
+            (Some(_), true) => 3,
+            (None, true) => 2,
+            (Some(_), false) => 1,
+            (None, false) => 0,
+        }
+    }
+}
+
+/// The files that are loaded, leaving out the example files, e.g. `.env.example`, that only
+/// document the variables.
+fn loaded(variables: &[(Variable, Metadata)]) -> impl Iterator<Item = Entry<'_>> {
+    variables.iter().filter(|(_, meta)| !is_example(&meta.path))
+}
+
+fn layer(meta: &Metadata) -> Result<Layer> {
+    match Layer::from_path(&meta.path) {
+        Some(layer) => Ok(layer),
+        None => bail!(
+            "{:?} isn't named like a layer of .env files, e.g. `.env`, `.env.local`, `.env.production` or `.env.production.local`",
+            meta.path
+        ),
+    }
+}
+
+/// The standard modes, along with the ones that the files are specific to.
+pub fn modes(variables: &[(Variable, Metadata)]) -> Result<Vec<String>> {
+    let mut modes = STANDARD_MODES.map(str::to_string).to_vec();
+
+    for (_, meta) in loaded(variables) {
+        if let Some(mode) = layer(meta)?.mode {
+            if !modes.contains(&mode) {
+                modes.push(mode);
+            }
+        }
+    }
+
+    Ok(modes)
+}
+
+/// The assignments of each variable that are loaded in the mode, from the one that wins to the
+/// ones it overrides.
+pub fn effective_variables<'v>(
+    variables: &'v [(Variable, Metadata)],
+    mode: &str,
+) -> Result<BTreeMap<&'v str, Vec<Entry<'v>>>> {
+    let mut effective: BTreeMap<&str, Vec<(u8, Entry)>> = BTreeMap::new();
+
+    for entry in loaded(variables) {
+        let layer = layer(&entry.1)?;
+
+        if layer.applies_to(mode) {
+            effective
+                .entry(&entry.0.key)
+                .or_default()
+                .push((layer.precedence(), entry));
+        }
+    }
+
+    Ok(effective
+        .into_iter()
+        .map(|(key, mut entries)| {
+            // among files of the same layer, the last one wins, as it's loaded last
+            entries.reverse();
+            entries.sort_by_key(|(precedence, _)| std::cmp::Reverse(*precedence));
+
+            (key, entries.into_iter().map(|(_, entry)| entry).collect())
+        })
+        .collect())
+}
+
+/// The modes in which each variable is set.
+pub fn modes_by_variable(
+    variables: &[(Variable, Metadata)],
+) -> Result<BTreeMap<&str, Vec<String>>> {
+    let mut modes_by_variable: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+
+    for mode in modes(variables)? {
+        for key in effective_variables(variables, &mode)?.into_keys() {
+            modes_by_variable.entry(key).or_default().push(mode.clone());
+        }
+    }
+
+    Ok(modes_by_variable)
+}
+
+/// Lists which file wins for each variable in the mode, i.e. the `NODE_ENV`, and the files it
+/// overrides.
+pub fn explain(variables: &[(Variable, Metadata)], mode: &str) -> Result<String> {
+    let effective = effective_variables(variables, mode)?;
+    let width = effective.keys().map(|key| key.len()).max().unwrap_or(0);
+
+    let lines = effective.iter().map(|(key, entries)| {
+        let file = |(_, meta): &Entry| meta.path.to_string_lossy().to_string();
+        let (winner, overridden) = entries.split_first().expect("a variable is set somewhere");
+
+        match overridden {
+            [] => format!("{key:width$}  {}", file(winner)),
+            overridden => format!(
+                "{key:width$}  {} (overrides {})",
+                file(winner),
+                overridden.iter().map(file).collect::<Vec<_>>().join(", ")
+            ),
+        }
+    });
+
+    Ok(std::iter::once(format!("NODE_ENV={mode}"))
+        .chain(lines)
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::dotenv::{
+        parse::{parse_variables, Variable},
+        profile::{explain, modes_by_variable, Layer},
+        zod::Metadata,
+    };
+
+    fn variables(files: &[(&str, &str)]) -> Vec<(Variable, Metadata)> {
+        files
+            .iter()
+            .flat_map(|(path, source)| {
+                let meta = Metadata::new(source, Path::new(path));
+
+                parse_variables(source)
+                    .unwrap()
+                    .into_iter()
+                    .map(move |var| (var, meta.clone()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn layering_env_files() {
+        let layer = |path: &str| Layer::from_path(Path::new(path));
+        let mode = |mode: &str, local| {
+            Some(Layer {
+                mode: Some(mode.to_string()),
+                local,
+            })
+        };
+
+        assert_eq!(
+            layer("app/.env"),
+            Some(Layer {
+                mode: None,
+                local: false
+            })
+        );
+        assert_eq!(
+            layer(".env.local"),
+            Some(Layer {
+                mode: None,
+                local: true
+            })
+        );
+        assert_eq!(layer(".env.production"), mode("production", false));
+        assert_eq!(layer(".env.test.local"), mode("test", true));
+        assert_eq!(layer(".env."), None);
+        assert_eq!(layer(".env.a.b"), None);
+        assert_eq!(layer("env.production"), None);
+        assert_eq!(layer(".envrc"), None);
+
+        let variables = variables(&[
+            (".env", "PORT=3000\nAPI_URL=https://example.com\n"),
+            (".env.local", "API_URL=http://localhost:8080\n"),
+            (".env.development", "DEBUG=true\n"),
+            (".env.development.local", "API_URL=http://localhost:9090\n"),
+            (".env.staging", "API_URL=https://staging.example.com\n"),
+            (".env.example", "API_URL=\nSENTRY_DSN=\n"),
+        ]);
+
+        assert_eq!(
+            explain(&variables, "development").unwrap(),
+            "NODE_ENV=development
+API_URL  .env.development.local (overrides .env.local, .env)
+DEBUG    .env.development
+PORT     .env"
+        );
+        assert_eq!(
+            explain(&variables, "test").unwrap(),
+            "NODE_ENV=test
+API_URL  .env
+PORT     .env"
+        );
+        assert_eq!(
+            explain(&variables, "staging").unwrap(),
+            "NODE_ENV=staging
+API_URL  .env.local (overrides .env.staging, .env)
+PORT     .env"
+        );
+
+        let modes = modes_by_variable(&variables).unwrap();
+        assert_eq!(modes["DEBUG"], ["development"]);
+        assert_eq!(
+            modes["API_URL"],
+            ["development", "production", "test", "staging"]
+        );
+
+        let err = modes_by_variable(&self::variables(&[("config.env", "PORT=80\n")]))
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("\"config.env\" isn't named like a layer of .env files"));
+    }
+}
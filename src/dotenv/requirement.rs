@@ -0,0 +1,180 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Result;
+
+use super::{parse::Variable, profile, typehint_parser::TypeHint, zod::Metadata};
+
+/// Whether a variable has to be set, decided once for every file that's generated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Requirement {
+    Required,
+    Optional,
+    /// Only required in these modes, the ones that set it, with `--profiles`.
+    InModes(Vec<String>),
+}
+
+/// Whether the file documents the variables rather than setting them, e.g. `.env.example`.
+pub fn is_example(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| {
+            name.split('.')
+                .any(|part| matches!(part, "example" | "sample" | "template"))
+        })
+}
+
+/// What the annotations of a variable say, in any of the files: `@required` wins over an
+/// optional type hint, and a `@default` makes it required.
+fn annotated(assignments: &[&Variable]) -> Option<Requirement> {
+    let type_hints = || assignments.iter().filter_map(|var| var.type_hint.as_ref());
+
+    if assignments
+        .iter()
+        .any(|var| var.annotations.iter().any(|a| a.tag() == "required"))
+        || type_hints().any(|(th, _)| matches!(th, TypeHint::Defaulted(..)))
+    {
+        Some(Requirement::Required)
+    } else if type_hints().any(|(th, _)| th.is_optional()) {
+        Some(Requirement::Optional)
+    } else {
+        None
+    }
+}
+
+/// Decides whether each variable is required, unless its annotations say otherwise, from where
+/// it's set: in every file, or with `profiles`, in every mode. Example files only count when
+/// they're the only files, so a variable that's only in an example file is optional.
+pub fn requirements(
+    variables: &[(Variable, Metadata)],
+    profiles: bool,
+) -> Result<BTreeMap<&str, Requirement>> {
+    let mut assignments: BTreeMap<&str, Vec<&(Variable, Metadata)>> = BTreeMap::new();
+
+    for entry in variables {
+        assignments.entry(&entry.0.key).or_default().push(entry);
+    }
+
+    let only_examples = variables.iter().all(|(_, meta)| is_example(&meta.path));
+    let counts = |meta: &Metadata| only_examples || !is_example(&meta.path);
+
+    let mut files = variables
+        .iter()
+        .filter(|(_, meta)| counts(meta))
+        .map(|(_, meta)| &meta.path)
+        .collect::<Vec<_>>();
+    files.dedup();
+
+    let modes = match profiles {
+        true => Some((
+            profile::modes(variables)?,
+            profile::modes_by_variable(variables)?,
+        )),
+        false => None,
+    };
+
+    Ok(assignments
+        .into_iter()
+        .map(|(key, entries)| {
+            let vars = entries.iter().map(|(var, _)| var).collect::<Vec<_>>();
+
+            let requirement = annotated(&vars).unwrap_or_else(|| match &modes {
+                Some((all, by_variable)) => match by_variable.get(key) {
+                    Some(modes) if modes == all => Requirement::Required,
+                    Some(modes) => Requirement::InModes(modes.clone()),
+                    None => Requirement::Optional,
+                },
+                None => {
+                    let mut set_in = entries
+                        .iter()
+                        .filter(|(_, meta)| counts(meta))
+                        .map(|(_, meta)| &meta.path)
+                        .collect::<Vec<_>>();
+                    set_in.dedup();
+
+                    match set_in.len() == files.len() {
+                        true => Requirement::Required,
+                        false => Requirement::Optional,
+                    }
+                }
+            });
+
+            (key, requirement)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::dotenv::{
+        parse::{parse_variables_with_type_hints, Variable},
+        requirement::{is_example, requirements, Requirement},
+        typehint_parser::TypeDefs,
+        zod::Metadata,
+    };
+
+    fn variables(files: &[(&str, &str)]) -> Vec<(Variable, Metadata)> {
+        files
+            .iter()
+            .flat_map(|(path, source)| {
+                let meta = Metadata::new(source, Path::new(path));
+
+                parse_variables_with_type_hints(&meta, &TypeDefs::new(), &mut vec![])
+                    .unwrap()
+                    .into_iter()
+                    .map(move |var| (var, meta.clone()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn deciding_requirements() {
+        assert!(is_example(Path::new("app/.env.example")));
+        assert!(is_example(Path::new(".env.local.sample")));
+        assert!(!is_example(Path::new(".env.examples")));
+
+        let variables = variables(&[
+            (
+                ".env.example",
+                "PORT=\nAPI_URL=\nSENTRY_DSN=\n# @required\nSECRET=\n",
+            ),
+            (
+                ".env.development",
+                "PORT=3000\nAPI_URL=http://localhost\nDEBUG=true\n",
+            ),
+            (
+                ".env.production",
+                "PORT=80\n# @type url?\nAPI_URL=https://example.com\n# @default 8\nWORKERS=\n",
+            ),
+        ]);
+
+        let requirements = requirements(&variables, false).unwrap();
+
+        assert_eq!(requirements["PORT"], Requirement::Required);
+        assert_eq!(requirements["API_URL"], Requirement::Optional);
+        assert_eq!(requirements["SENTRY_DSN"], Requirement::Optional);
+        assert_eq!(requirements["DEBUG"], Requirement::Optional);
+        assert_eq!(requirements["SECRET"], Requirement::Required);
+        assert_eq!(requirements["WORKERS"], Requirement::Required);
+
+        let examples = self::variables(&[(".env.example", "PORT=\n# @optional\nAPI_URL=\n")]);
+        let requirements = super::requirements(&examples, false).unwrap();
+
+        assert_eq!(requirements["PORT"], Requirement::Required);
+        assert_eq!(requirements["API_URL"], Requirement::Optional);
+
+        let layers = self::variables(&[
+            (".env", "PORT=3000\n"),
+            (".env.example", "PORT=\nDEBUG=\n"),
+            (".env.development", "DEBUG=true\n"),
+        ]);
+        let requirements = super::requirements(&layers, true).unwrap();
+
+        assert_eq!(requirements["PORT"], Requirement::Required);
+        assert_eq!(
+            requirements["DEBUG"],
+            Requirement::InModes(vec!["development".to_string()])
+        );
+    }
+}
@@ -1,11 +1,636 @@
-use std::fmt::Display;
+use std::{collections::BTreeMap, fmt::Display};
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+use regex::{Regex, RegexBuilder};
+
+use super::config::CustomType;
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum TypeHint {
+    String,
+    /// e.g. `number`, `int`, `port` or `number(1..65535)`, where the bounds are inclusive.
+    Number {
+        integer: bool,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    Boolean,
+    /// A string in a well known format.
+    Format(Format),
+    Union(Box<[Literal]>),
+    /// A list of values in one variable, e.g. `string[]` or `('a' | 'b')[] sep=';'`.
+    Array {
+        element: Box<TypeHint>,
+        separator: Box<str>,
+    },
+    /// A date, e.g. `date`, or a timestamp, e.g. `datetime`, coerced to a `Date` unless kept as an
+    /// ISO 8601 string, e.g. `datetime(iso)`.
+    Date {
+        time: bool,
+        iso: bool,
+    },
+    /// A human friendly amount, e.g. `30s` or `512MB`, converted to a number of `unit`s, as in
+    /// `duration(s)` or `bytes(KiB)`.
+    Quantity {
+        quantity: Quantity,
+        unit: &'static str,
+    },
+    /// A JSON document, e.g. `json` or `json<{ free: number; pro: number }>`.
+    Json(JsonShape),
+    /// A string shaped like a TypeScript template literal type, e.g. `` `https://${string}` ``.
+    Template(Box<[TemplatePart]>),
+    /// A string type hint, further constrained by a `@pattern`.
+    Matching(Box<TypeHint>, Pattern),
+    /// A type hint checked against the value once it's gone through the `@transform`s, or against
+    /// each of its parts when it's split.
+    Transformed(Box<TypeHint>, Box<[Transform]>),
+    /// The variable may be left unset, e.g. `number?` or `'a' | 'b' | undefined`.
+    Optional(Box<TypeHint>),
+    /// The variable falls back to a `@default` value when it's unset, which makes it required
+    /// once parsed.
+    Defaulted(Box<TypeHint>, Box<str>),
+    /// A reference to a type declared with `@typedef`, along with what it's defined as.
+    Named(Box<str>, Box<TypeHint>),
+    /// A type defined in the project's config, along with its definition.
+    Custom(Box<str>, Box<CustomType>),
+}
+
+/// The types declared with `@typedef Name <type>`, and the custom types of the config, by name.
+pub type TypeDefs = BTreeMap<Box<str>, TypeHint>;
+
+static NO_TYPEDEFS: TypeDefs = BTreeMap::new();
+
+impl TypeHint {
+    pub const NUMBER: TypeHint = TypeHint::Number {
+        integer: false,
+        min: None,
+        max: None,
+    };
+
+    pub fn is_optional(&self) -> bool {
+        match self {
+            TypeHint::Optional(_) => true,
+            TypeHint::Named(_, th) => th.is_optional(),
+            _ => false,
+        }
+    }
+
+    /// Wraps the type hint as optional, unless it already is.
+    pub fn optional(self) -> Self {
+        match self {
+            TypeHint::Optional(_) => self,
+            th => TypeHint::Optional(Box::new(th)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Format {
+    Url,
+    Email,
+    Uuid,
+    Ip,
+    Hostname,
+    Base64,
+}
+
+impl Format {
+    const ALL: [Format; 6] = [
+        Format::Url,
+        Format::Email,
+        Format::Uuid,
+        Format::Ip,
+        Format::Hostname,
+        Format::Base64,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Url => "url",
+            Format::Email => "email",
+            Format::Uuid => "uuid",
+            Format::Ip => "ip",
+            Format::Hostname => "hostname",
+            Format::Base64 => "base64",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.name() == name)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Quantity {
+    Duration,
+    Bytes,
+}
+
+impl Quantity {
+    const ALL: [Quantity; 2] = [Quantity::Duration, Quantity::Bytes];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Quantity::Duration => "duration",
+            Quantity::Bytes => "bytes",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|quantity| quantity.name() == name)
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Quantity::Duration => "a duration, like `30s` or `5m`",
+            Quantity::Bytes => "a byte size, like `512MB` or `1GiB`",
+        }
+    }
+
+    /// The units an amount can be written in, with how many of the first one they're worth.
+    pub fn units(&self) -> &'static [(&'static str, f64)] {
+        match self {
+            Quantity::Duration => &[
+                ("ms", 1.0),
+                ("s", 1e3),
+                ("m", 6e4),
+                ("h", 3.6e6),
+                ("d", 8.64e7),
+            ],
+            Quantity::Bytes => &[
+                ("B", 1.0),
+                ("KB", 1e3),
+                ("MB", 1e6),
+                ("GB", 1e9),
+                ("TB", 1e12),
+                ("KiB", 1024.0),
+                ("MiB", 1048576.0),
+                ("GiB", 1073741824.0),
+                ("TiB", 1099511627776.0),
+            ],
+        }
+    }
+
+    /// Finds a unit by its name, ignoring case, e.g. `mb` for `MB`.
+    pub fn unit(&self, name: &str) -> Option<(&'static str, f64)> {
+        self.units()
+            .iter()
+            .copied()
+            .find(|(unit, _)| unit.eq_ignore_ascii_case(name))
+    }
+}
+
+/// The expected shape of a JSON value, written like a TypeScript type.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum JsonShape {
+    /// Any JSON value, from a plain `json` type hint.
+    Unknown,
+    String,
+    Number,
+    Boolean,
+    Union(Box<[Literal]>),
+    Array(Box<JsonShape>),
+    Object(Box<[JsonField]>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct JsonField {
+    pub name: Box<str>,
+    pub shape: JsonShape,
+    pub optional: bool,
+}
+
+impl Display for JsonShape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonShape::Unknown => f.write_str("unknown"),
+            JsonShape::String => f.write_str("string"),
+            JsonShape::Number => f.write_str("number"),
+            JsonShape::Boolean => f.write_str("boolean"),
+            JsonShape::Union(values) => f.write_str(
+                &values
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" | "),
+            ),
+            JsonShape::Array(element) => match element.as_ref() {
+                JsonShape::Union(_) => write!(f, "({element})[]"),
+                _ => write!(f, "{element}[]"),
+            },
+            JsonShape::Object(fields) if fields.is_empty() => f.write_str("{}"),
+            JsonShape::Object(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|field| {
+                        let optional = if field.optional { "?" } else { "" };
+                        format!("{}{optional}: {}", field.name, field.shape)
+                    })
+                    .collect::<Vec<_>>();
+
+                write!(f, "{{ {} }}", fields.join("; "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TemplatePart {
+    Text(Box<str>),
+    /// `${string}`, `${number}` or `${boolean}`.
+    Placeholder(Placeholder),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Placeholder {
     String,
     Number,
     Boolean,
-    Union(Box<[Box<str>]>),
+}
+
+impl Placeholder {
+    const ALL: [Placeholder; 3] = [
+        Placeholder::String,
+        Placeholder::Number,
+        Placeholder::Boolean,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Placeholder::String => "string",
+            Placeholder::Number => "number",
+            Placeholder::Boolean => "boolean",
+        }
+    }
+}
+
+/// A regex matching the same strings as the template literal type, which works both in Rust and
+/// JavaScript.
+pub fn template_regex(parts: &[TemplatePart]) -> String {
+    let parts = parts
+        .iter()
+        .map(|part| match part {
+            TemplatePart::Text(text) => regex::escape(text),
+            TemplatePart::Placeholder(Placeholder::String) => ".*".to_string(),
+            TemplatePart::Placeholder(Placeholder::Number) => r"-?\d+(\.\d+)?".to_string(),
+            TemplatePart::Placeholder(Placeholder::Boolean) => "(true|false)".to_string(),
+        })
+        .collect::<String>();
+
+    format!("^{parts}$")
+}
+
+/// A regular expression from a `@pattern /source/flags` annotation.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Pattern {
+    pub source: Box<str>,
+    pub flags: Box<str>,
+}
+
+impl Pattern {
+    pub fn to_regex(&self) -> Result<Regex, regex::Error> {
+        RegexBuilder::new(&self.source)
+            .case_insensitive(self.flags.contains('i'))
+            .multi_line(self.flags.contains('m'))
+            .dot_matches_new_line(self.flags.contains('s'))
+            .build()
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "/{}/{}", self.source, self.flags)
+    }
+}
+
+/// The source of a regex, with its unescaped `/` escaped, to put it between slashes in JavaScript.
+pub fn escape_slashes(source: &str) -> String {
+    let mut escaped = String::with_capacity(source.len());
+    let mut backslash = false;
+
+    for c in source.chars() {
+        if c == '/' && !backslash {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        backslash = c == '\\' && !backslash;
+    }
+
+    escaped
+}
+
+/// Finds the syntax that the Rust regex understands but JavaScript doesn't, as a message, along
+/// with its position and width in the source.
+pub fn rust_only_syntax(source: &str) -> Option<(String, usize, usize)> {
+    let mut class = false;
+    let mut chars = source.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        let rest = &source[i..];
+
+        match c {
+            '\\' => match chars.next() {
+                Some((_, 'A')) => {
+                    return Some(("unsupported `\\A`, use `^` instead".to_string(), i, 2))
+                }
+                Some((_, 'z')) => {
+                    return Some(("unsupported `\\z`, use `$` instead".to_string(), i, 2))
+                }
+                Some((_, p @ ('p' | 'P'))) if !rest[2..].starts_with('{') => {
+                    let width = 2 + rest[2..].chars().next().map_or(0, char::len_utf8);
+
+                    return Some((
+                        format!(
+                            "unsupported `{}`, use `\\{p}{{{}}}` with the `u` flag instead",
+                            &rest[..width],
+                            &rest[2..width]
+                        ),
+                        i,
+                        width,
+                    ));
+                }
+                _ => {}
+            },
+            '[' if class && rest.starts_with("[:") => {
+                let width = rest.find(":]").map_or(rest.len(), |end| end + 2);
+
+                return Some((
+                    format!(
+                        "unsupported POSIX class `{}`, use a range like `a-z` or an escape like `\\d` instead",
+                        &rest[..width]
+                    ),
+                    i,
+                    width,
+                ));
+            }
+            '[' if class => {
+                return Some((
+                    "unsupported nested class, escape the `[` as `\\[` instead".to_string(),
+                    i,
+                    1,
+                ))
+            }
+            '&' | '-' | '~' if class && rest[1..].starts_with(c) => {
+                return Some((format!("unsupported class set operation `{c}{c}`"), i, 2))
+            }
+            '[' => class = true,
+            ']' => class = false,
+            '(' if !class && rest.starts_with("(?P<") => {
+                return Some((
+                    "unsupported `(?P<`, use `(?<` for named groups instead".to_string(),
+                    i,
+                    4,
+                ))
+            }
+            '(' if !class && rest.starts_with("(?") && !rest[2..].starts_with([':', '<']) => {
+                let width = rest.find([')', ':']).map_or(rest.len(), |end| end + 1);
+
+                return Some((
+                    format!(
+                        "unsupported inline flags `{}`, use flags after the closing `/` instead",
+                        &rest[..width]
+                    ),
+                    i,
+                    width,
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Parses the annotation `@pattern /source/flags`, making sure the regex compiles, and means the
+/// same in JavaScript.
+pub fn parse_pattern(text: &str) -> Result<Pattern, TypeHintError> {
+    let error = |message: &str, position: usize, width: usize| TypeHintError {
+        message: message.to_string(),
+        position,
+        width,
+    };
+
+    let tag_end = text.find(char::is_whitespace).unwrap_or(text.len());
+    let body = text[tag_end..].trim_start();
+    let start = text.len() - body.len();
+
+    if !body.starts_with('/') {
+        return Err(error(
+            "expected a regex between slashes, like /^[a-z]+$/",
+            start,
+            body.len(),
+        ));
+    }
+
+    let body = body.trim_end();
+    let end = match body.rfind('/') {
+        Some(end) if end > 0 => end,
+        _ => {
+            return Err(error(
+                "unterminated regex, expected a closing `/`",
+                start,
+                1,
+            ))
+        }
+    };
+
+    let pattern = Pattern {
+        source: body[1..end].into(),
+        flags: body[end + 1..].into(),
+    };
+
+    if let Some((i, flag)) = pattern
+        .flags
+        .char_indices()
+        .find(|(_, flag)| !matches!(flag, 'i' | 'm' | 's' | 'u'))
+    {
+        return Err(error(
+            &format!("unsupported regex flag `{flag}`, expected any of `i`, `m`, `s` or `u`"),
+            start + end + 1 + i,
+            flag.len_utf8(),
+        ));
+    }
+
+    if let Err(e) = pattern.to_regex() {
+        let e = e.to_string();
+        let reason = e
+            .lines()
+            .find_map(|line| line.strip_prefix("error: "))
+            .unwrap_or(&e);
+
+        return Err(error(
+            &format!("invalid regex: {reason}"),
+            start + 1,
+            pattern.source.len(),
+        ));
+    }
+
+    if let Some((message, position, width)) = rust_only_syntax(&pattern.source) {
+        return Err(error(&message, start + 1 + position, width));
+    }
+
+    Ok(pattern)
+}
+
+/// A step of a `@transform` annotation, e.g. `@transform trim lowercase split(',')`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Transform {
+    Trim,
+    Lowercase,
+    Uppercase,
+    /// Decodes base64 data into UTF-8 text.
+    Base64,
+    /// Splits the value on a separator, applying the transforms after it to each part.
+    Split(Box<str>),
+}
+
+impl Transform {
+    const NAMES: [&'static str; 5] = ["trim", "lowercase", "uppercase", "base64", "split"];
+}
+
+impl Display for Transform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transform::Trim => f.write_str("trim"),
+            Transform::Lowercase => f.write_str("lowercase"),
+            Transform::Uppercase => f.write_str("uppercase"),
+            Transform::Base64 => f.write_str("base64"),
+            Transform::Split(sep) if sep.contains('\'') => write!(f, "split(\"{sep}\")"),
+            Transform::Split(sep) => write!(f, "split('{sep}')"),
+        }
+    }
+}
+
+/// Parses the annotation `@transform trim lowercase split(',')`, whose transforms are applied in
+/// order, with at most one `split`.
+pub fn parse_transforms(text: &str) -> Result<Box<[Transform]>, TypeHintError> {
+    let error = |message: String, position: usize, width: usize| TypeHintError {
+        message,
+        position,
+        width,
+    };
+
+    let mut position = text.find(char::is_whitespace).unwrap_or(text.len());
+    let mut transforms = Vec::new();
+
+    loop {
+        position += text[position..].len() - text[position..].trim_start().len();
+
+        if position == text.len() {
+            break;
+        }
+
+        let start = position;
+        let name_len = text[start..]
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(text.len() - start);
+        let name = &text[start..start + name_len];
+        position += name_len;
+
+        let transform = match name {
+            "trim" => Transform::Trim,
+            "lowercase" => Transform::Lowercase,
+            "uppercase" => Transform::Uppercase,
+            "base64" => Transform::Base64,
+            "split" => {
+                let rest = &text[position..];
+                let sep = rest
+                    .strip_prefix('(')
+                    .and_then(|rest| {
+                        let quote = rest.chars().next().filter(|&c| c == '\'' || c == '"')?;
+                        let (sep, _) = rest[1..].split_once(quote)?;
+
+                        rest[sep.len() + 2..].starts_with(')').then_some(sep)
+                    })
+                    .ok_or_else(|| {
+                        error(
+                            "expected a quoted separator, like split(',')".to_string(),
+                            start,
+                            rest.find(char::is_whitespace).unwrap_or(rest.len()) + name_len,
+                        )
+                    })?;
+
+                position += sep.len() + 4;
+
+                if sep.is_empty() {
+                    return Err(error(
+                        "the separator can't be empty".to_string(),
+                        start,
+                        position - start,
+                    ));
+                }
+                if transforms.iter().any(|t| matches!(t, Transform::Split(_))) {
+                    return Err(error(
+                        "a value can only be split once".to_string(),
+                        start,
+                        position - start,
+                    ));
+                }
+
+                Transform::Split(sep.into())
+            }
+            _ => {
+                let width = text[start..]
+                    .find(char::is_whitespace)
+                    .unwrap_or(text.len() - start);
+                let found = &text[start..start + width];
+
+                return Err(error(
+                    match did_you_mean(found, Transform::NAMES.into_iter()) {
+                        Some(name) => format!("unknown transform `{found}`, did you mean `{name}`?"),
+                        None => format!(
+                            "unknown transform `{found}`, expected any of `trim`, `lowercase`, `uppercase`, `base64` or `split(',')`"
+                        ),
+                    },
+                    start,
+                    width,
+                ));
+            }
+        };
+
+        transforms.push(transform);
+    }
+
+    if transforms.is_empty() {
+        return Err(error(
+            "expected some transforms, like `trim lowercase`".to_string(),
+            position,
+            0,
+        ));
+    }
+
+    Ok(transforms.into())
+}
+
+/// A member of a union type hint.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Literal {
+    /// The contents of a single or double quoted string.
+    String(Box<str>),
+    /// The number as written, e.g. `8080` or `-0.5`.
+    Number(Box<str>),
+    Boolean(bool),
+}
+
+impl Literal {
+    fn from_token(token: &Token) -> Self {
+        match token.kind {
+            TokenKind::NumberLiteral => Literal::Number(token.text.into()),
+            TokenKind::BooleanLiteral => Literal::Boolean(token.text == "true"),
+            _ => Literal::String(token.text[1..token.text.len() - 1].into()),
+        }
+    }
+}
+
+impl Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Literal::String(s) => write!(f, "'{}'", s.replace('\\', "\\\\").replace('\'', "\\'")),
+            Literal::Number(n) => f.write_str(n),
+            Literal::Boolean(b) => write!(f, "{b}"),
+        }
+    }
 }
 
 impl Display for TypeHint {
@@ -13,9 +638,72 @@ impl Display for TypeHint {
         let s = match self {
             TypeHint::Union(values) => values
                 .iter()
-                .map(|a| a.as_ref())
+                .map(ToString::to_string)
                 .collect::<Vec<_>>()
                 .join(" | "),
+            TypeHint::Optional(th) => format!("{th}?"),
+            TypeHint::Defaulted(th, default) => format!("{th} @default {default}"),
+            TypeHint::Named(name, _) | TypeHint::Custom(name, _) => name.to_string(),
+            TypeHint::Template(parts) => {
+                let parts = parts
+                    .iter()
+                    .map(|part| match part {
+                        TemplatePart::Text(text) => text
+                            .replace('\\', "\\\\")
+                            .replace('`', "\\`")
+                            .replace("${", "\\${"),
+                        TemplatePart::Placeholder(placeholder) => {
+                            format!("${{{}}}", placeholder.name())
+                        }
+                    })
+                    .collect::<String>();
+
+                format!("`{parts}`")
+            }
+            TypeHint::Format(format) => format.name().to_string(),
+            TypeHint::Matching(th, pattern) => format!("{th} {pattern}"),
+            TypeHint::Transformed(th, transforms) => format!(
+                "{th} @transform {}",
+                transforms
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            TypeHint::Date { time, iso } => {
+                let name = if *time { "datetime" } else { "date" };
+
+                match iso {
+                    true => format!("{name}(iso)"),
+                    false => name.to_string(),
+                }
+            }
+            TypeHint::Quantity { quantity, unit } if *unit == quantity.units()[0].0 => {
+                quantity.name().to_string()
+            }
+            TypeHint::Quantity { quantity, unit } => format!("{}({unit})", quantity.name()),
+            TypeHint::Json(JsonShape::Unknown) => "json".to_string(),
+            TypeHint::Json(shape) => format!("json<{shape}>"),
+            TypeHint::Array { element, separator } => {
+                let sep = match separator.as_ref() {
+                    "," => String::new(),
+                    sep => format!(" sep={}", Literal::String(sep.into())),
+                };
+
+                match element.as_ref() {
+                    TypeHint::Union(_) => format!("({element})[]{sep}"),
+                    _ => format!("{element}[]{sep}"),
+                }
+            }
+            TypeHint::Number { integer, min, max } => {
+                let name = if *integer { "int" } else { "number" };
+                let bound = |b: &Option<f64>| b.map(|b| b.to_string()).unwrap_or_default();
+
+                match (min, max) {
+                    (None, None) => name.to_string(),
+                    _ => format!("{name}({}..{})", bound(min), bound(max)),
+                }
+            }
             tk => format!("{tk:?}").to_lowercase(),
         };
 
@@ -23,33 +711,195 @@ impl Display for TypeHint {
     }
 }
 
+/// A malformed type hint.
+#[derive(Debug, Error, PartialEq, Eq, Clone)]
+#[error("{message}")]
+pub struct TypeHintError {
+    pub message: String,
+    /// Byte offset, into the type hint's source, of the offending token.
+    pub position: usize,
+    /// Length in bytes of the offending token.
+    pub width: usize,
+}
+
+impl From<ParseError<'_>> for TypeHintError {
+    fn from(error: ParseError<'_>) -> Self {
+        let found = error.found();
+
+        Self {
+            message: error.to_string(),
+            position: found.position,
+            width: found.text.len(),
+        }
+    }
+}
+
 pub trait ParseTyeHint {
-    fn into_type_hint(self) -> Option<TypeHint>;
+    fn into_type_hint(self) -> Result<TypeHint, TypeHintError>;
 }
 
 impl ParseTyeHint for &str {
-    fn into_type_hint(self) -> Option<TypeHint> {
-        Parser::new(self).parse().ok()
+    fn into_type_hint(self) -> Result<TypeHint, TypeHintError> {
+        (self, &NO_TYPEDEFS).into_type_hint()
+    }
+}
+
+/// A type hint that can refer to the given `@typedef`s.
+impl ParseTyeHint for (&str, &TypeDefs) {
+    fn into_type_hint(self) -> Result<TypeHint, TypeHintError> {
+        let (source, typedefs) = self;
+
+        Parser::new(source)
+            .with_typedefs(typedefs)
+            .parse()
+            .map_err(TypeHintError::from)
     }
 }
 
+/// Whether the name can be given to a `@typedef`, or a custom type, as opposed to being a built-in
+/// type or not an identifier at all.
+pub fn is_type_name(name: &str) -> bool {
+    let mut lexer = Lexer::new(name);
+
+    lexer.next_token().kind == TokenKind::Ident && lexer.next_token().kind == TokenKind::Eof
+}
+
+/// The names that the generated module declares or imports, which a `@typedef` can't take.
+pub const RESERVED_NAMES: [&str; 21] = [
+    "z",
+    "ZodTypeAny",
+    "dotenv",
+    "clientEnvSchemas",
+    "serverEnvSchemas",
+    "processEnv",
+    "readEnv",
+    "REFERENCE",
+    "expand",
+    "ClientEnv",
+    "clientEnv",
+    "Env",
+    "env",
+    "cache",
+    "lookupEnv",
+    "BadEnvError",
+    "duration",
+    "bytes",
+    "inModes",
+    "decodeBase64",
+    "quantity",
+];
+
+/// Parses the annotation `@typedef Name <type>`, whose type can refer to the given, previously
+/// declared, `@typedef`s.
+pub fn parse_typedef(
+    text: &str,
+    typedefs: &TypeDefs,
+) -> Result<(Box<str>, TypeHint), TypeHintError> {
+    Parser::new(text)
+        .with_typedefs(typedefs)
+        .parse_typedef()
+        .map_err(TypeHintError::from)
+}
+
 #[derive(PartialEq, Debug, Clone, Copy)]
 enum TokenKind {
     Keyword,
+    TypedefKeyword,
     Pound,
     StringType,
     NumberType,
+    IntType,
+    PortType,
     BooleanType,
+    FormatType,
+    QuantityType,
+    DateType,
+    JsonType,
+    Undefined,
+    Ident,
     StringLiteral,
+    TemplateLiteral,
+    NumberLiteral,
+    BooleanLiteral,
     Pipe,
+    Question,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    DotDot,
+    Equals,
+    Comma,
+    LAngle,
+    RAngle,
+    LBrace,
+    RBrace,
+    Colon,
+    Semicolon,
     Eof,
     Illegal,
 }
 
+impl Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            TokenKind::Keyword => "`@type`",
+            TokenKind::TypedefKeyword => "`@typedef`",
+            TokenKind::Pound => "`#`",
+            TokenKind::StringType => "`string`",
+            TokenKind::NumberType => "`number`",
+            TokenKind::IntType => "`int`",
+            TokenKind::PortType => "`port`",
+            TokenKind::BooleanType => "`boolean`",
+            TokenKind::FormatType => "a string format",
+            TokenKind::QuantityType => "`duration` or `bytes`",
+            TokenKind::DateType => "`date` or `datetime`",
+            TokenKind::JsonType => "`json`",
+            TokenKind::Undefined => "`undefined`",
+            TokenKind::Ident => "a type name",
+            TokenKind::StringLiteral => "a string literal",
+            TokenKind::TemplateLiteral => "a template literal",
+            TokenKind::NumberLiteral => "a number literal",
+            TokenKind::BooleanLiteral => "a boolean literal",
+            TokenKind::Pipe => "`|`",
+            TokenKind::Question => "`?`",
+            TokenKind::LParen => "`(`",
+            TokenKind::RParen => "`)`",
+            TokenKind::LBracket => "`[`",
+            TokenKind::RBracket => "`]`",
+            TokenKind::DotDot => "`..`",
+            TokenKind::Equals => "`=`",
+            TokenKind::Comma => "`,`",
+            TokenKind::LAngle => "`<`",
+            TokenKind::RAngle => "`>`",
+            TokenKind::LBrace => "`{`",
+            TokenKind::RBrace => "`}`",
+            TokenKind::Colon => "`:`",
+            TokenKind::Semicolon => "`;`",
+            TokenKind::Eof => "the end of the type hint",
+            TokenKind::Illegal => "an illegal character",
+        };
+
+        f.write_str(s)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 struct Token<'source> {
     kind: TokenKind,
     text: &'source str,
+    /// Byte offset of the token in the source.
+    position: usize,
+}
+
+impl Display for Token<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            TokenKind::Eof => write!(f, "{}", self.kind),
+            _ if self.text.contains('`') => write!(f, "`` {} ``", self.text),
+            _ => write!(f, "`{}`", self.text),
+        }
+    }
 }
 
 struct Lexer<'source> {
@@ -65,148 +915,191 @@ impl<'source> Lexer<'source> {
         }
     }
 
-    fn char(&self) -> Option<&'source u8> {
-        self.source.as_bytes().get(self.position)
+    fn char(&self) -> Option<char> {
+        self.source[self.position..].chars().next()
     }
 
-    fn char_skipping_whitespace(&mut self) -> Option<&'source u8> {
-        while self
-            .char()
-            .map(|&c| c.is_ascii_whitespace())
-            .unwrap_or(false)
-        {
+    fn char_skipping_whitespace(&mut self) -> Option<char> {
+        while self.char().map(char::is_whitespace).unwrap_or(false) {
             self.step();
         }
 
-        return self.char();
+        self.char()
     }
 
+    /// Moves past the current character, however many bytes it takes.
     fn step(&mut self) {
-        self.position += 1;
+        self.position += self.char().map(char::len_utf8).unwrap_or(0);
     }
 
     pub fn next_token(&mut self) -> Token<'source> {
         let Some(ch) = self.char_skipping_whitespace() else {
-            return Token{
+            return Token {
                 kind: TokenKind::Eof,
-                text: ""
-            }
+                text: "",
+                position: self.source.len(),
+            };
         };
 
-        let token = match ch {
-            b'@' => self.lex_keyword("@type"),
-            b'\'' => self.lex_string_literal(),
-            b'|' => Token {
-                kind: TokenKind::Pipe,
-                text: "|",
-            },
+        let start = self.position;
+
+        let kind = match ch {
+            '@' => self.lex_keyword(),
+            quote @ ('\'' | '"') => self.lex_string_literal(quote),
+            '`' => self.lex_template_literal(),
+            c if c.is_ascii_digit() || c == '-' => self.lex_number_literal(),
             c if c.is_ascii_alphabetic() => self.lex_type(),
-            b'#' => Token {
-                kind: TokenKind::Pound,
-                text: "#",
-            },
-            _ => Token {
-                kind: TokenKind::Illegal,
-                text: &self.source[self.position..self.position + 1],
-            },
+            c => {
+                self.step();
+                match c {
+                    '|' => TokenKind::Pipe,
+                    '#' => TokenKind::Pound,
+                    '?' => TokenKind::Question,
+                    '(' => TokenKind::LParen,
+                    ')' => TokenKind::RParen,
+                    '[' => TokenKind::LBracket,
+                    ']' => TokenKind::RBracket,
+                    '=' => TokenKind::Equals,
+                    ',' => TokenKind::Comma,
+                    '<' => TokenKind::LAngle,
+                    '>' => TokenKind::RAngle,
+                    '{' => TokenKind::LBrace,
+                    '}' => TokenKind::RBrace,
+                    ':' => TokenKind::Colon,
+                    ';' => TokenKind::Semicolon,
+                    '.' if self.char() == Some('.') => {
+                        self.step();
+                        TokenKind::DotDot
+                    }
+                    _ => TokenKind::Illegal,
+                }
+            }
         };
 
-        self.step();
-
-        token
+        Token {
+            kind,
+            text: &self.source[start..self.position],
+            position: start,
+        }
     }
 
-    fn lex_type(&mut self) -> Token<'source> {
+    fn lex_type(&mut self) -> TokenKind {
         let start = self.position;
 
         while self
             .char()
-            .map(|&c| c.is_ascii_lowercase())
+            .map(|c| c.is_ascii_alphanumeric() || c == '_')
             .unwrap_or(false)
         {
             self.step();
         }
 
-        let s = &self.source[start..self.position];
-
-        match s {
-            "string" => Token {
-                kind: TokenKind::StringType,
-                text: s,
-            },
-            "number" => Token {
-                kind: TokenKind::NumberType,
-                text: s,
-            },
-            "boolean" => Token {
-                kind: TokenKind::BooleanType,
-                text: s,
-            },
-            _ => Token {
-                kind: TokenKind::Illegal,
-                text: s,
-            },
+        match &self.source[start..self.position] {
+            name if Format::from_name(name).is_some() => TokenKind::FormatType,
+            name if Quantity::from_name(name).is_some() => TokenKind::QuantityType,
+            "string" => TokenKind::StringType,
+            "number" => TokenKind::NumberType,
+            "int" => TokenKind::IntType,
+            "port" => TokenKind::PortType,
+            "boolean" => TokenKind::BooleanType,
+            "date" | "datetime" => TokenKind::DateType,
+            "json" => TokenKind::JsonType,
+            "undefined" => TokenKind::Undefined,
+            "true" | "false" => TokenKind::BooleanLiteral,
+            _ => TokenKind::Ident,
         }
     }
 
-    fn lex_keyword(&mut self, keyword: &str) -> Token<'source> {
+    fn lex_keyword(&mut self) -> TokenKind {
         let start = self.position;
 
         self.step();
 
-        while self
-            .char()
-            .map(|&c| c.is_ascii_lowercase())
-            .unwrap_or(false)
-        {
+        while self.char().map(|c| c.is_ascii_lowercase()).unwrap_or(false) {
             self.step();
         }
 
-        let s = &self.source[start..self.position];
-
-        if s == keyword {
-            return Token {
-                kind: TokenKind::Keyword,
-                text: s,
-            };
+        match &self.source[start..self.position] {
+            "@type" => TokenKind::Keyword,
+            "@typedef" => TokenKind::TypedefKeyword,
+            _ => TokenKind::Illegal,
         }
-
-        return Token {
-            kind: TokenKind::Illegal,
-            text: s,
-        };
     }
 
-    fn lex_string_literal(&mut self) -> Token<'source> {
-        let start = self.position;
-
+    fn lex_string_literal(&mut self, quote: char) -> TokenKind {
         self.step();
 
-        while self.char().map(|&c| c != b'\'').unwrap_or(false) {
+        while self.char().map(|c| c != quote).unwrap_or(false) {
             self.step();
         }
 
-        let Some(b'\'') = self.char() else {
-            let s = &self.source[start..self.position];
-            return Token {
-                kind: TokenKind::Illegal,
-                text: s,
-            };
-        };
+        if self.char() != Some(quote) {
+            return TokenKind::Illegal;
+        }
 
         self.step();
 
-        let s = &self.source[start..self.position];
-
-        return Token {
-            kind: TokenKind::StringLiteral,
-            text: s,
-        };
+        TokenKind::StringLiteral
     }
-}
 
-impl<'source> Iterator for Lexer<'source> {
-    type Item = Token<'source>;
+    /// Lexes a template literal as a whole, leaving its placeholders to the parser, e.g.
+    /// `` `redis://${string}` ``.
+    fn lex_template_literal(&mut self) -> TokenKind {
+        self.step();
+
+        loop {
+            match self.char() {
+                None => return TokenKind::Illegal,
+                Some('`') => break,
+                Some('\\') => {
+                    self.step();
+                    self.step();
+                }
+                Some(_) => self.step(),
+            }
+        }
+
+        self.step();
+
+        TokenKind::TemplateLiteral
+    }
+
+    fn skip_digits(&mut self) {
+        while self.char().map(|c| c.is_ascii_digit()).unwrap_or(false) {
+            self.step();
+        }
+    }
+
+    fn lex_number_literal(&mut self) -> TokenKind {
+        let start = self.position;
+
+        if self.char() == Some('-') {
+            self.step();
+        }
+
+        self.skip_digits();
+
+        // a dot only belongs to the number if a digit follows, as opposed to a range like `1..10`
+        let rest = &self.source[self.position..];
+        if rest.starts_with('.') && rest[1..].starts_with(|c: char| c.is_ascii_digit()) {
+            self.step();
+            self.skip_digits();
+        }
+
+        let digits = self.source[start..self.position].trim_start_matches('-');
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, "0"));
+        let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit());
+
+        if all_digits(whole) && all_digits(fraction) {
+            return TokenKind::NumberLiteral;
+        }
+
+        TokenKind::Illegal
+    }
+}
+
+impl<'source> Iterator for Lexer<'source> {
+    type Item = Token<'source>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let token = self.next_token();
@@ -215,29 +1108,126 @@ impl<'source> Iterator for Lexer<'source> {
             return None;
         }
 
-        return Some(token);
+        Some(token)
     }
 }
 
 use thiserror::Error;
 
+const TYPES: [&str; 16] = [
+    "string", "number", "int", "port", "boolean", "url", "email", "uuid", "ip", "hostname",
+    "base64", "date", "datetime", "duration", "bytes", "json",
+];
+
 #[derive(Debug, Error)]
 enum ParseError<'source> {
-    #[error("expected to find {expected:?} but found {found:?}")]
+    #[error("expected {expected} but found {found}")]
     ExpectedToken {
         expected: TokenKind,
         found: Token<'source>,
     },
-    #[error("unexpected end of input")]
-    UnexpectedEnd,
-    #[error("unexpected token found: {found:?}")]
+    #[error("expected a type after `@type`")]
+    UnexpectedEnd { found: Token<'source> },
+    #[error("unexpected {found}{}", straight_quotes_hint(found.text))]
     IllegalToken { found: Token<'source> },
+    #[error("unknown type {found}, expected one of {} or a union of 'literals'{}", TYPES.map(|ty| format!("`{ty}`")).join(", "), suggestion.map(|ty| format!(", did you mean `{ty}`?")).unwrap_or_default())]
+    UnknownType {
+        found: Token<'source>,
+        /// The closest known type, if there is one close enough.
+        suggestion: Option<&'source str>,
+    },
+    #[error("unterminated string literal {found}")]
+    UnterminatedString { found: Token<'source> },
+    #[error("expected a literal, like 'a', \"a\", 1 or true, but found {found}")]
+    ExpectedLiteral { found: Token<'source> },
+    #[error("unknown constraint {found}, expected `min` or `max`")]
+    UnknownConstraint { found: Token<'source> },
+    #[error("the range {found} is empty, its minimum is greater than its maximum")]
+    EmptyRange { found: Token<'source> },
+    #[error("the separator of an array can't be empty")]
+    EmptySeparator { found: Token<'source> },
+    #[error("unknown unit {found} for {}, expected one of {}", quantity.name(), quantity.units().iter().map(|(unit, _)| format!("`{unit}`")).collect::<Vec<_>>().join(", "))]
+    UnknownUnit {
+        quantity: Quantity,
+        found: Token<'source>,
+    },
+    #[error(
+        "unknown placeholder {found}, expected `${{string}}`, `${{number}}` or `${{boolean}}`"
+    )]
+    UnknownPlaceholder { found: Token<'source> },
+    #[error("unknown modifier {found}, expected `iso`")]
+    UnknownModifier { found: Token<'source> },
+    #[error("expected a JSON type, like `string`, `number`, `boolean`, a 'literal' or an object `{{ key: number }}`, but found {found}")]
+    ExpectedJsonShape { found: Token<'source> },
+    #[error("expected a field name, like `key` or 'key', but found {found}")]
+    ExpectedFieldName { found: Token<'source> },
+    #[error("the name {found} is already declared in the generated module")]
+    ReservedName { found: Token<'source> },
+}
+
+impl<'source> ParseError<'source> {
+    fn found(&self) -> &Token<'source> {
+        match self {
+            ParseError::ExpectedToken { found, .. }
+            | ParseError::UnexpectedEnd { found }
+            | ParseError::IllegalToken { found }
+            | ParseError::UnknownType { found, .. }
+            | ParseError::UnterminatedString { found }
+            | ParseError::ExpectedLiteral { found }
+            | ParseError::UnknownConstraint { found }
+            | ParseError::EmptyRange { found }
+            | ParseError::EmptySeparator { found }
+            | ParseError::UnknownUnit { found, .. }
+            | ParseError::UnknownModifier { found }
+            | ParseError::UnknownPlaceholder { found }
+            | ParseError::ExpectedJsonShape { found }
+            | ParseError::ExpectedFieldName { found }
+            | ParseError::ReservedName { found } => found,
+        }
+    }
+}
+
+/// Curly quotes tend to sneak in when copying from docs or chat apps.
+fn straight_quotes_hint(text: &str) -> &'static str {
+    match text {
+        "\u{2018}" | "\u{2019}" | "\u{201c}" | "\u{201d}" => {
+            ", string literals need straight single quotes: 'like this'"
+        }
+        _ => "",
+    }
+}
+
+fn did_you_mean<'a>(word: &str, types: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    types
+        .map(|ty| (ty, edit_distance(word, ty)))
+        .filter(|(_, distance)| *distance <= 2)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(ty, _)| ty)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+
+    row[b.len()]
 }
 
 struct Parser<'source> {
     lexer: Lexer<'source>,
     token: Token<'source>,
     peeked: Option<Token<'source>>,
+    typedefs: &'source TypeDefs,
 }
 
 impl<'source> Parser<'source> {
@@ -247,9 +1237,16 @@ impl<'source> Parser<'source> {
             peeked: None,
             token: lexer.next_token(),
             lexer,
+            typedefs: &NO_TYPEDEFS,
         }
     }
 
+    /// Lets the type hint refer to these `@typedef`s.
+    fn with_typedefs(mut self, typedefs: &'source TypeDefs) -> Self {
+        self.typedefs = typedefs;
+        self
+    }
+
     fn next_token(&mut self) -> &Token<'source> {
         self.token = match self.peeked.take() {
             Some(t) => t,
@@ -272,26 +1269,476 @@ impl<'source> Parser<'source> {
 
         self.next_token();
 
-        match self.token.kind {
-            TokenKind::StringType => return Ok(TypeHint::String),
-            TokenKind::NumberType => return Ok(TypeHint::Number),
-            TokenKind::BooleanType => return Ok(TypeHint::Boolean),
-            TokenKind::StringLiteral => {
-                let mut union: Vec<Box<str>> = vec![self.token.text.into()];
+        self.parse_body()
+    }
+
+    pub fn parse_typedef(&mut self) -> Result<(Box<str>, TypeHint), ParseError<'source>> {
+        if self.token.kind == TokenKind::Pound {
+            self.next_token();
+        }
+
+        self.expect(TokenKind::TypedefKeyword)?;
+        self.next_token();
+        self.expect(TokenKind::Ident)?;
+
+        if RESERVED_NAMES.contains(&self.token.text) {
+            return Err(ParseError::ReservedName { found: self.token });
+        }
+
+        let name = self.token.text.into();
+
+        self.next_token();
+
+        Ok((name, self.parse_body()?))
+    }
+
+    /// Parses what follows `@type`, or the name of a `@typedef`, up to the end of the type hint.
+    fn parse_body(&mut self) -> Result<TypeHint, ParseError<'source>> {
+        let mut optional = false;
+
+        let (mut th, can_be_array) = match self.token.kind {
+            TokenKind::StringLiteral | TokenKind::NumberLiteral | TokenKind::BooleanLiteral => {
+                (self.parse_union(Some(&mut optional))?, false)
+            }
+            // a union needs parentheses to be the element of an array, e.g. `('a' | 'b')[]`
+            TokenKind::LParen => {
+                self.next_token();
+                self.expect_literal()?;
+                let union = self.parse_union(None)?;
+                self.expect(TokenKind::RParen)?;
+                self.next_token();
+                self.expect(TokenKind::LBracket)?;
+
+                (union, true)
+            }
+            _ => (self.parse_type()?, true),
+        };
+
+        if can_be_array && self.token.kind == TokenKind::LBracket {
+            self.next_token();
+            self.expect(TokenKind::RBracket)?;
+            self.next_token();
+
+            let mut separator: Box<str> = ",".into();
+
+            if self.token.kind == TokenKind::Ident && self.token.text == "sep" {
+                self.next_token();
+                self.expect(TokenKind::Equals)?;
+                self.next_token();
+                self.expect(TokenKind::StringLiteral)?;
+
+                separator = match Literal::from_token(&self.token) {
+                    Literal::String(s) if !s.is_empty() => s,
+                    _ => return Err(ParseError::EmptySeparator { found: self.token }),
+                };
+
+                self.next_token();
+            }
+
+            th = TypeHint::Array {
+                element: Box::new(th),
+                separator,
+            };
+        }
+
+        // a type can only be made optional, not mixed with other types or literals
+        while self.token.kind == TokenKind::Pipe {
+            while self.next_token().kind == TokenKind::Pipe {}
+            self.expect(TokenKind::Undefined)?;
+            optional = true;
+            self.next_token();
+        }
+
+        if self.token.kind == TokenKind::Question {
+            optional = true;
+            self.next_token();
+        }
+
+        self.expect(TokenKind::Eof)?;
+
+        if optional {
+            return Ok(th.optional());
+        }
+
+        Ok(th)
+    }
+
+    /// Parses a type, e.g. `string` or `int(1..)`, leaving the token following it as the current
+    /// one.
+    fn parse_type(&mut self) -> Result<TypeHint, ParseError<'source>> {
+        let kind = self.token.kind;
+
+        let mut th = match kind {
+            TokenKind::StringType => TypeHint::String,
+            TokenKind::NumberType => TypeHint::NUMBER,
+            TokenKind::IntType => TypeHint::Number {
+                integer: true,
+                min: None,
+                max: None,
+            },
+            TokenKind::PortType => TypeHint::Number {
+                integer: true,
+                min: Some(1.0),
+                max: Some(65535.0),
+            },
+            TokenKind::BooleanType => TypeHint::Boolean,
+            TokenKind::FormatType => TypeHint::Format(
+                Format::from_name(self.token.text).expect("format types are lexed by their name"),
+            ),
+            TokenKind::TemplateLiteral => self.parse_template()?,
+            TokenKind::DateType => TypeHint::Date {
+                time: self.token.text == "datetime",
+                iso: false,
+            },
+            TokenKind::QuantityType => {
+                let quantity = Quantity::from_name(self.token.text)
+                    .expect("quantity types are lexed by their name");
+
+                TypeHint::Quantity {
+                    quantity,
+                    unit: quantity.units()[0].0,
+                }
+            }
+            TokenKind::JsonType => {
+                self.next_token();
+
+                if self.token.kind != TokenKind::LAngle {
+                    return Ok(TypeHint::Json(JsonShape::Unknown));
+                }
+
+                self.next_token();
+                let shape = self.parse_json_shape()?;
+                self.expect(TokenKind::RAngle)?;
+                self.next_token();
+
+                return Ok(TypeHint::Json(shape));
+            }
+            TokenKind::Eof => return Err(ParseError::UnexpectedEnd { found: self.token }),
+            TokenKind::Ident => match self.typedefs.get_key_value(self.token.text) {
+                // custom types are referred to directly, like built-in ones
+                Some((_, th @ TypeHint::Custom(..))) => th.clone(),
+                Some((name, th)) => TypeHint::Named(name.clone(), Box::new(th.clone())),
+                None => {
+                    let known = TYPES
+                        .into_iter()
+                        .chain(self.typedefs.keys().map(AsRef::as_ref));
+
+                    return Err(ParseError::UnknownType {
+                        found: self.token,
+                        suggestion: did_you_mean(self.token.text, known),
+                    });
+                }
+            },
+            TokenKind::Illegal if self.token.text.starts_with(['\'', '"', '`']) => {
+                return Err(ParseError::UnterminatedString { found: self.token })
+            }
+            _ => return Err(ParseError::IllegalToken { found: self.token }),
+        };
+
+        self.next_token();
+
+        if let TypeHint::Number { min, max, .. } = &mut th {
+            if self.token.kind == TokenKind::LParen
+                && matches!(kind, TokenKind::NumberType | TokenKind::IntType)
+            {
+                (*min, *max) = self.parse_bounds()?;
+                self.next_token();
+            }
+        }
+
+        if let TypeHint::Date { iso, .. } = &mut th {
+            if self.token.kind == TokenKind::LParen {
+                self.next_token();
+
+                if self.token.text != "iso" {
+                    return Err(ParseError::UnknownModifier { found: self.token });
+                }
+
+                *iso = true;
+
+                self.next_token();
+                self.expect(TokenKind::RParen)?;
+                self.next_token();
+            }
+        }
+
+        if let TypeHint::Quantity { quantity, unit } = &mut th {
+            if self.token.kind == TokenKind::LParen {
+                self.next_token();
+
+                *unit = match quantity.unit(self.token.text) {
+                    Some((name, _)) if self.token.kind == TokenKind::Ident => name,
+                    _ => {
+                        return Err(ParseError::UnknownUnit {
+                            quantity: *quantity,
+                            found: self.token,
+                        })
+                    }
+                };
+
+                self.next_token();
+                self.expect(TokenKind::RParen)?;
+                self.next_token();
+            }
+        }
+
+        Ok(th)
+    }
+
+    /// Parses a union of literals, starting at the current literal token, and leaving the token
+    /// following it as the current one. `undefined` is only allowed when it can make the union
+    /// optional.
+    fn parse_union(
+        &mut self,
+        mut optional: Option<&mut bool>,
+    ) -> Result<TypeHint, ParseError<'source>> {
+        let mut union = vec![Literal::from_token(&self.token)];
+
+        self.next_token();
+
+        while self.token.kind == TokenKind::Pipe {
+            // just ignore any bunch of consecutive pipes
+            while self.next_token().kind == TokenKind::Pipe {}
+
+            match (self.token.kind, optional.as_deref_mut()) {
+                (TokenKind::Undefined, Some(optional)) => *optional = true,
+                _ => {
+                    self.expect_literal()?;
+                    union.push(Literal::from_token(&self.token));
+                }
+            }
+
+            self.next_token();
+        }
+
+        Ok(TypeHint::Union(union.into()))
+    }
+
+    /// Splits the current template literal token into text and `${type}` placeholders.
+    fn parse_template(&self) -> Result<TypeHint, ParseError<'source>> {
+        let token = self.token;
+        let inner = &token.text[1..token.text.len() - 1];
+
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = inner.char_indices().peekable();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => text.extend(chars.next().map(|(_, c)| c)),
+                '$' if chars.peek().is_some_and(|(_, c)| *c == '{') => {
+                    let end = inner[i..].find('}').map(|end| i + end + 1);
+                    let placeholder = end.and_then(|end| {
+                        let name = inner[i + 2..end - 1].trim();
+                        Placeholder::ALL.into_iter().find(|p| p.name() == name)
+                    });
+
+                    let Some((end, placeholder)) = end.zip(placeholder) else {
+                        let end = end.unwrap_or(inner.len());
+
+                        return Err(ParseError::UnknownPlaceholder {
+                            found: Token {
+                                kind: TokenKind::Illegal,
+                                text: &inner[i..end],
+                                position: token.position + 1 + i,
+                            },
+                        });
+                    };
+
+                    if !text.is_empty() {
+                        parts.push(TemplatePart::Text(std::mem::take(&mut text).into()));
+                    }
+                    parts.push(TemplatePart::Placeholder(placeholder));
+
+                    while chars.next_if(|(j, _)| *j < end).is_some() {}
+                }
+                c => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            parts.push(TemplatePart::Text(text.into()));
+        }
+
+        Ok(TypeHint::Template(parts.into()))
+    }
+
+    /// Parses the shape of a JSON value, e.g. `number[]` or `{ free: number; pro?: number }`,
+    /// leaving the token following it as the current one.
+    fn parse_json_shape(&mut self) -> Result<JsonShape, ParseError<'source>> {
+        let mut shape = match self.token.kind {
+            TokenKind::StringType => JsonShape::String,
+            TokenKind::NumberType => JsonShape::Number,
+            TokenKind::BooleanType => JsonShape::Boolean,
+            TokenKind::LBrace => self.parse_json_object()?,
+            TokenKind::LParen => {
+                self.next_token();
+                self.expect_literal()?;
+                let TypeHint::Union(literals) = self.parse_union(None)? else {
+                    unreachable!("unions are parsed as unions")
+                };
+                self.expect(TokenKind::RParen)?;
+
+                JsonShape::Union(literals)
+            }
+            TokenKind::StringLiteral | TokenKind::NumberLiteral | TokenKind::BooleanLiteral => {
+                let TypeHint::Union(literals) = self.parse_union(None)? else {
+                    unreachable!("unions are parsed as unions")
+                };
+
+                return Ok(JsonShape::Union(literals));
+            }
+            TokenKind::Illegal if self.token.text.starts_with(['\'', '"']) => {
+                return Err(ParseError::UnterminatedString { found: self.token })
+            }
+            _ => return Err(ParseError::ExpectedJsonShape { found: self.token }),
+        };
+
+        self.next_token();
+
+        // like at the top level, a union needs parentheses to be the element of an array
+        while self.token.kind == TokenKind::LBracket {
+            self.next_token();
+            self.expect(TokenKind::RBracket)?;
+            self.next_token();
+
+            shape = JsonShape::Array(Box::new(shape));
+        }
+
+        Ok(shape)
+    }
+
+    /// Parses an object, e.g. `{ free: number; pro?: number }`, whose fields can be separated by
+    /// `;` or `,`, leaving the closing brace as the current token.
+    fn parse_json_object(&mut self) -> Result<JsonShape, ParseError<'source>> {
+        let mut fields = vec![];
+
+        self.next_token();
 
-                while self.next_token().kind == TokenKind::Pipe && self.token.kind != TokenKind::Eof
+        while self.token.kind != TokenKind::RBrace {
+            let name = match self.token.kind {
+                TokenKind::StringLiteral => match Literal::from_token(&self.token) {
+                    Literal::String(name) => name,
+                    _ => unreachable!("string literals are strings"),
+                },
+                _ if self
+                    .token
+                    .text
+                    .starts_with(|c: char| c.is_ascii_alphabetic()) =>
                 {
-                    // just ignore any bunch of consecutive pipes
-                    while self.next_token().kind == TokenKind::Pipe {}
-                    union.push(self.token.text.into());
+                    self.token.text.into()
                 }
+                _ => return Err(ParseError::ExpectedFieldName { found: self.token }),
+            };
+
+            self.next_token();
+
+            let optional = self.token.kind == TokenKind::Question;
+            if optional {
+                self.next_token();
+            }
+
+            self.expect(TokenKind::Colon)?;
+            self.next_token();
+
+            fields.push(JsonField {
+                name,
+                shape: self.parse_json_shape()?,
+                optional,
+            });
+
+            match self.token.kind {
+                TokenKind::Semicolon | TokenKind::Comma => {
+                    self.next_token();
+                }
+                _ => self.expect(TokenKind::RBrace)?,
+            }
+        }
+
+        Ok(JsonShape::Object(fields.into()))
+    }
+
+    /// Parses either a range, `(1..65535)`, `(1..)`, or named bounds, `(min=1, max=10)`, leaving
+    /// the closing parenthesis as the current token.
+    fn parse_bounds(&mut self) -> Result<(Option<f64>, Option<f64>), ParseError<'source>> {
+        let opening = self.token;
+        let (mut min, mut max) = (None, None);
+
+        self.next_token();
+
+        if self.token.kind == TokenKind::Ident {
+            loop {
+                let bound = match self.token.text {
+                    "min" => &mut min,
+                    "max" => &mut max,
+                    _ => return Err(ParseError::UnknownConstraint { found: self.token }),
+                };
+
+                self.next_token();
+                self.expect(TokenKind::Equals)?;
+                self.next_token();
+                *bound = Some(self.number()?);
+                self.next_token();
+
+                if self.token.kind != TokenKind::Comma {
+                    break;
+                }
+
+                self.next_token();
+            }
+        } else {
+            if self.token.kind == TokenKind::NumberLiteral {
+                min = Some(self.number()?);
+                self.next_token();
+            }
+
+            self.expect(TokenKind::DotDot)?;
+            self.next_token();
+
+            if self.token.kind == TokenKind::NumberLiteral {
+                max = Some(self.number()?);
+                self.next_token();
+            }
+        }
+
+        self.expect(TokenKind::RParen)?;
+
+        if let (Some(min), Some(max)) = (min, max) {
+            if min > max {
+                let end = self.token.position + self.token.text.len();
+
+                return Err(ParseError::EmptyRange {
+                    found: Token {
+                        kind: TokenKind::Illegal,
+                        text: &self.lexer.source[opening.position..end],
+                        position: opening.position,
+                    },
+                });
+            }
+        }
+
+        Ok((min, max))
+    }
+
+    fn number(&self) -> Result<f64, ParseError<'source>> {
+        self.expect(TokenKind::NumberLiteral)?;
 
-                return Ok(TypeHint::Union(union.into()));
+        Ok(self
+            .token
+            .text
+            .parse()
+            .expect("number literals should have been lexed as valid numbers"))
+    }
+
+    fn expect_literal(&self) -> Result<(), ParseError<'source>> {
+        match self.token.kind {
+            TokenKind::StringLiteral | TokenKind::NumberLiteral | TokenKind::BooleanLiteral => {
+                Ok(())
             }
-            TokenKind::Eof => return Err(ParseError::UnexpectedEnd),
-            TokenKind::Pipe | TokenKind::Illegal | TokenKind::Keyword | TokenKind::Pound => {
-                return Err(ParseError::IllegalToken { found: self.token })
+            TokenKind::Illegal if self.token.text.starts_with(['\'', '"']) => {
+                Err(ParseError::UnterminatedString { found: self.token })
             }
+            _ => Err(ParseError::ExpectedLiteral { found: self.token }),
         }
     }
 
@@ -310,8 +1757,13 @@ impl<'source> Parser<'source> {
 #[cfg(test)]
 mod tests {
     use insta::assert_debug_snapshot;
+    use proptest::prelude::*;
 
-    use crate::dotenv::typehint_parser::{Lexer, Parser};
+    use crate::dotenv::typehint_parser::{
+        escape_slashes, parse_pattern, parse_transforms, template_regex, Format, JsonField,
+        JsonShape, Lexer, Literal, ParseTyeHint, Parser, Pattern, Placeholder, Quantity,
+        TemplatePart, Transform, TypeHint, TypeHintError,
+    };
 
     #[test]
     fn lexing_type_hints() {
@@ -322,6 +1774,7 @@ mod tests {
         assert_debug_snapshot!(Lexer::new("@type boolean").collect::<Vec<_>>());
         assert_debug_snapshot!(Lexer::new("@type 'qa' | 'dev' | 'prod'").collect::<Vec<_>>());
         assert_debug_snapshot!(Lexer::new("# @type 'qa' | 'dev' | 'prod'").collect::<Vec<_>>());
+        assert_debug_snapshot!(Lexer::new("# @type ’qa’ | 'café'").collect::<Vec<_>>());
     }
 
     #[test]
@@ -332,5 +1785,618 @@ mod tests {
         assert_debug_snapshot!(Parser::new("@type boolean").parse());
         assert_debug_snapshot!(Parser::new("@type 'qa' | 'dev' | 'prod'").parse());
         assert_debug_snapshot!(Parser::new("@type 'qa' || 'dev' ||| | 'prod' | || 'test'").parse());
+        assert_debug_snapshot!(Parser::new("@type 'qa'|'dev'").parse());
+        assert_debug_snapshot!(Parser::new("@type 'café' | '日本'").parse());
+    }
+
+    #[test]
+    fn parse_optional_type_hints() {
+        let parse = |source: &str| source.into_type_hint().unwrap();
+        let optional = |th| TypeHint::Optional(Box::new(th));
+        let union = |values: &[&str]| {
+            TypeHint::Union(values.iter().map(|&v| Literal::String(v.into())).collect())
+        };
+
+        assert_eq!(parse("@type number?"), optional(TypeHint::NUMBER));
+        assert_eq!(
+            parse("@type string | undefined"),
+            optional(TypeHint::String)
+        );
+        assert_eq!(
+            parse("@type boolean|undefined?"),
+            optional(TypeHint::Boolean)
+        );
+        assert_eq!(parse("@type 'a' | 'b'?"), optional(union(&["a", "b"])));
+        assert_eq!(
+            parse("@type 'a' | undefined | 'b'"),
+            optional(union(&["a", "b"]))
+        );
+        assert_eq!(parse("@type 'a' | 'b'"), union(&["a", "b"]));
+    }
+
+    #[test]
+    fn parse_literal_unions() {
+        let parse = |source: &str| source.into_type_hint().unwrap();
+        let string = |s: &str| Literal::String(s.into());
+        let number = |n: &str| Literal::Number(n.into());
+
+        assert_eq!(
+            parse("@type 1 | 2 | -3.5"),
+            TypeHint::Union([number("1"), number("2"), number("-3.5")].into())
+        );
+        assert_eq!(
+            parse(r#"@type "a" | 'b' | "it's""#),
+            TypeHint::Union([string("a"), string("b"), string("it's")].into())
+        );
+        assert_eq!(
+            parse("@type 'auto' | false | 0"),
+            TypeHint::Union([string("auto"), Literal::Boolean(false), number("0")].into())
+        );
+        assert_eq!(
+            parse("@type 007 | 1.50 | -0.25"),
+            TypeHint::Union([number("007"), number("1.50"), number("-0.25")].into())
+        );
+        assert_eq!(
+            parse("@type true?"),
+            TypeHint::Optional(Box::new(TypeHint::Union([Literal::Boolean(true)].into())))
+        );
+        assert_eq!(
+            parse(r#"@type "it's" | 'a\b'"#).to_string(),
+            r#"'it\'s' | 'a\\b'"#
+        );
+    }
+
+    #[test]
+    fn parse_number_constraints() {
+        let parse = |source: &str| source.into_type_hint().unwrap();
+        let number = |integer, min, max| TypeHint::Number { integer, min, max };
+
+        assert_eq!(parse("@type int"), number(true, None, None));
+        assert_eq!(parse("@type port"), number(true, Some(1.0), Some(65535.0)));
+        assert_eq!(
+            parse("@type number(1..65535)"),
+            number(false, Some(1.0), Some(65535.0))
+        );
+        assert_eq!(
+            parse("@type number(-0.5..)"),
+            number(false, Some(-0.5), None)
+        );
+        assert_eq!(parse("@type int(..10)"), number(true, None, Some(10.0)));
+        assert_eq!(parse("@type int(min=1)"), number(true, Some(1.0), None));
+        assert_eq!(
+            parse("@type int( max = 8, min = 2 )?"),
+            TypeHint::Optional(Box::new(number(true, Some(2.0), Some(8.0))))
+        );
+        assert_eq!(parse("@type int(min=1)").to_string(), "int(1..)");
+    }
+
+    #[test]
+    fn parse_string_formats() {
+        let parse = |source: &str| source.into_type_hint().unwrap();
+
+        assert_eq!(parse("@type url"), TypeHint::Format(Format::Url));
+        assert_eq!(parse("@type email"), TypeHint::Format(Format::Email));
+        assert_eq!(parse("@type uuid"), TypeHint::Format(Format::Uuid));
+        assert_eq!(parse("@type ip"), TypeHint::Format(Format::Ip));
+        assert_eq!(parse("@type hostname"), TypeHint::Format(Format::Hostname));
+        assert_eq!(
+            parse("@type base64 | undefined"),
+            TypeHint::Optional(Box::new(TypeHint::Format(Format::Base64)))
+        );
+    }
+
+    #[test]
+    fn parse_patterns() {
+        let pattern = |source: &str, flags: &str| Pattern {
+            source: source.into(),
+            flags: flags.into(),
+        };
+        let error = |message: &str, position, width| TypeHintError {
+            message: message.to_string(),
+            position,
+            width,
+        };
+
+        assert_eq!(
+            parse_pattern("@pattern /sk_live_[A-Za-z0-9]{24}/"),
+            Ok(pattern("sk_live_[A-Za-z0-9]{24}", ""))
+        );
+        assert_eq!(
+            parse_pattern(r"@pattern   /^[a-z]{2}-[a-z]+-\d$/i "),
+            Ok(pattern(r"^[a-z]{2}-[a-z]+-\d$", "i"))
+        );
+        assert_eq!(parse_pattern("@pattern /a/b/"), Ok(pattern("a/b", "")));
+        assert_eq!(
+            parse_pattern("@pattern ^[a-z]+$"),
+            Err(error(
+                "expected a regex between slashes, like /^[a-z]+$/",
+                9,
+                8
+            ))
+        );
+        assert_eq!(
+            parse_pattern("@pattern /[a-z]+"),
+            Err(error("unterminated regex, expected a closing `/`", 9, 1))
+        );
+        assert_eq!(
+            parse_pattern("@pattern /[a-z]+/gi"),
+            Err(error(
+                "unsupported regex flag `g`, expected any of `i`, `m`, `s` or `u`",
+                17,
+                1
+            ))
+        );
+        assert_eq!(
+            parse_pattern("@pattern /[a-/"),
+            Err(error("invalid regex: unclosed character class", 10, 3))
+        );
+        assert_eq!(
+            parse_pattern(r"@pattern /(?i)^[a-z]+$/"),
+            Err(error(
+                "unsupported inline flags `(?i)`, use flags after the closing `/` instead",
+                10,
+                4
+            ))
+        );
+        assert_eq!(
+            parse_pattern(r"@pattern /\A\d+\z/"),
+            Err(error(r"unsupported `\A`, use `^` instead", 10, 2))
+        );
+        assert_eq!(
+            parse_pattern(r"@pattern /^(?P<major>\d+)$/"),
+            Err(error(
+                "unsupported `(?P<`, use `(?<` for named groups instead",
+                11,
+                4
+            ))
+        );
+        assert_eq!(
+            parse_pattern(r"@pattern /^[[:alpha:]]+$/"),
+            Err(error(
+                r"unsupported POSIX class `[:alpha:]`, use a range like `a-z` or an escape like `\d` instead",
+                12,
+                9
+            ))
+        );
+        assert_eq!(
+            parse_pattern(r"@pattern /^[a-z&&[^aeiou]]+$/"),
+            Err(error("unsupported class set operation `&&`", 15, 2))
+        );
+        assert_eq!(
+            parse_pattern(r"@pattern /^[\w--\d]+$/"),
+            Err(error("unsupported class set operation `--`", 14, 2))
+        );
+        assert_eq!(
+            parse_pattern(r"@pattern /^\pN+$/"),
+            Err(error(
+                r"unsupported `\pN`, use `\p{N}` with the `u` flag instead",
+                11,
+                3
+            ))
+        );
+        assert_eq!(
+            parse_pattern(r"@pattern /^(?:(?<x>a)|[(?i)\\A])$/"),
+            Ok(pattern(r"^(?:(?<x>a)|[(?i)\\A])$", ""))
+        );
+
+        assert_eq!(escape_slashes(r"a/b\/c\\/d"), r"a\/b\/c\\\/d");
+    }
+
+    #[test]
+    fn parse_transform_annotations() {
+        let error = |message: &str, position, width| TypeHintError {
+            message: message.to_string(),
+            position,
+            width,
+        };
+
+        assert_eq!(
+            parse_transforms("@transform trim lowercase"),
+            Ok([Transform::Trim, Transform::Lowercase].into())
+        );
+        assert_eq!(
+            parse_transforms("@transform  base64 split(',')   uppercase "),
+            Ok([
+                Transform::Base64,
+                Transform::Split(",".into()),
+                Transform::Uppercase
+            ]
+            .into())
+        );
+        assert_eq!(
+            parse_transforms(r#"@transform split("'")"#),
+            Ok([Transform::Split("'".into())].into())
+        );
+        assert_eq!(
+            parse_transforms("@transform"),
+            Err(error(
+                "expected some transforms, like `trim lowercase`",
+                10,
+                0
+            ))
+        );
+        assert_eq!(
+            parse_transforms("@transform trim lowercsae"),
+            Err(error(
+                "unknown transform `lowercsae`, did you mean `lowercase`?",
+                16,
+                9
+            ))
+        );
+        assert_eq!(
+            parse_transforms("@transform reverse"),
+            Err(error(
+                "unknown transform `reverse`, expected any of `trim`, `lowercase`, `uppercase`, `base64` or `split(',')`",
+                11,
+                7
+            ))
+        );
+        assert_eq!(
+            parse_transforms("@transform split(,)"),
+            Err(error("expected a quoted separator, like split(',')", 11, 8))
+        );
+        assert_eq!(
+            parse_transforms("@transform split(';"),
+            Err(error("expected a quoted separator, like split(',')", 11, 8))
+        );
+        assert_eq!(
+            parse_transforms("@transform split('')"),
+            Err(error("the separator can't be empty", 11, 9))
+        );
+        assert_eq!(
+            parse_transforms("@transform split(',') split(':')"),
+            Err(error("a value can only be split once", 22, 10))
+        );
+    }
+
+    #[test]
+    fn parse_arrays() {
+        let parse = |source: &str| source.into_type_hint().unwrap();
+        let array = |element, separator: &str| TypeHint::Array {
+            element: Box::new(element),
+            separator: separator.into(),
+        };
+
+        assert_eq!(parse("@type string[]"), array(TypeHint::String, ","));
+        assert_eq!(
+            parse("@type int(1..)[] sep=';'?"),
+            TypeHint::Optional(Box::new(array(
+                TypeHint::Number {
+                    integer: true,
+                    min: Some(1.0),
+                    max: None
+                },
+                ";"
+            )))
+        );
+        assert_eq!(
+            parse("@type ('a' | 'b')[] sep=\" \""),
+            array(
+                TypeHint::Union([Literal::String("a".into()), Literal::String("b".into())].into()),
+                " "
+            )
+        );
+        assert_eq!(
+            parse("@type ('a'|'b')[] sep=';'").to_string(),
+            "('a' | 'b')[] sep=';'"
+        );
+        assert_eq!(parse("@type url[]").to_string(), "url[]");
+    }
+
+    #[test]
+    fn parse_template_literals() {
+        let parse = |source: &str| source.into_type_hint().unwrap();
+        let text = |text: &str| TemplatePart::Text(text.into());
+
+        assert_eq!(
+            parse("@type `https://${string}`"),
+            TypeHint::Template(
+                [
+                    text("https://"),
+                    TemplatePart::Placeholder(Placeholder::String)
+                ]
+                .into()
+            )
+        );
+        assert_eq!(
+            parse("@type `${ number }ms` | undefined"),
+            TypeHint::Optional(Box::new(TypeHint::Template(
+                [TemplatePart::Placeholder(Placeholder::Number), text("ms")].into()
+            )))
+        );
+        assert_eq!(
+            parse(r"@type `a\`b\${c}${boolean}`").to_string(),
+            r"`a\`b\${c}${boolean}`"
+        );
+        assert_eq!(
+            template_regex(&[
+                text("redis://"),
+                TemplatePart::Placeholder(Placeholder::String),
+                text(":"),
+                TemplatePart::Placeholder(Placeholder::Number)
+            ]),
+            r"^redis://.*:-?\d+(\.\d+)?$"
+        );
+    }
+
+    #[test]
+    fn parse_dates() {
+        let parse = |source: &str| source.into_type_hint().unwrap();
+
+        assert_eq!(
+            parse("@type date"),
+            TypeHint::Date {
+                time: false,
+                iso: false
+            }
+        );
+        assert_eq!(
+            parse("@type datetime(iso)?"),
+            TypeHint::Optional(Box::new(TypeHint::Date {
+                time: true,
+                iso: true
+            }))
+        );
+        assert_eq!(parse("@type date( iso )").to_string(), "date(iso)");
+        assert_eq!(parse("@type datetime").to_string(), "datetime");
+    }
+
+    #[test]
+    fn parse_quantities() {
+        let parse = |source: &str| source.into_type_hint().unwrap();
+        let duration = |unit| TypeHint::Quantity {
+            quantity: Quantity::Duration,
+            unit,
+        };
+        let bytes = |unit| TypeHint::Quantity {
+            quantity: Quantity::Bytes,
+            unit,
+        };
+
+        assert_eq!(parse("@type duration"), duration("ms"));
+        assert_eq!(parse("@type duration(s)"), duration("s"));
+        assert_eq!(parse("@type bytes"), bytes("B"));
+        assert_eq!(
+            parse("@type bytes(mib)?"),
+            TypeHint::Optional(Box::new(bytes("MiB")))
+        );
+        assert_eq!(parse("@type duration(ms)").to_string(), "duration");
+        assert_eq!(parse("@type bytes(kb)[]").to_string(), "bytes(KB)[]");
+    }
+
+    #[test]
+    fn parse_json() {
+        let parse = |source: &str| source.into_type_hint().unwrap();
+        let field = |name: &str, shape, optional| JsonField {
+            name: name.into(),
+            shape,
+            optional,
+        };
+
+        assert_eq!(parse("@type json"), TypeHint::Json(JsonShape::Unknown));
+        assert_eq!(
+            parse("@type json?"),
+            TypeHint::Optional(Box::new(TypeHint::Json(JsonShape::Unknown)))
+        );
+        assert_eq!(
+            parse("@type json<{ free: number; pro: number }>"),
+            TypeHint::Json(JsonShape::Object(
+                [
+                    field("free", JsonShape::Number, false),
+                    field("pro", JsonShape::Number, false)
+                ]
+                .into()
+            ))
+        );
+        assert_eq!(
+            parse("@type json<{ 'api-key'?: string, tags: string[][]; nested: {}, }[]>"),
+            TypeHint::Json(JsonShape::Array(Box::new(JsonShape::Object(
+                [
+                    field("api-key", JsonShape::String, true),
+                    field(
+                        "tags",
+                        JsonShape::Array(Box::new(JsonShape::Array(Box::new(JsonShape::String)))),
+                        false
+                    ),
+                    field("nested", JsonShape::Object([].into()), false),
+                ]
+                .into()
+            ))))
+        );
+        assert_eq!(
+            parse("@type json<{ plan: 'free' | 'pro'; tiers: (1 | 2)[] }>").to_string(),
+            "json<{ plan: 'free' | 'pro'; tiers: (1 | 2)[] }>"
+        );
+        assert_eq!(parse("@type json<boolean>").to_string(), "json<boolean>");
+    }
+
+    #[test]
+    fn type_hint_errors() {
+        let err = |source: &str| source.into_type_hint().unwrap_err();
+        let error = |message: &str, position, width| TypeHintError {
+            message: message.to_string(),
+            position,
+            width,
+        };
+
+        assert_eq!(
+            err("@type nubmer"),
+            error("unknown type `nubmer`, expected one of `string`, `number`, `int`, `port`, `boolean`, `url`, `email`, `uuid`, `ip`, `hostname`, `base64`, `date`, `datetime`, `duration`, `bytes`, `json` or a union of 'literals', did you mean `number`?", 6, 6)
+        );
+        assert_eq!(
+            err("@type Stage"),
+            error("unknown type `Stage`, expected one of `string`, `number`, `int`, `port`, `boolean`, `url`, `email`, `uuid`, `ip`, `hostname`, `base64`, `date`, `datetime`, `duration`, `bytes`, `json` or a union of 'literals'", 6, 5)
+        );
+        assert_eq!(
+            err("@type 'qa"),
+            error("unterminated string literal `'qa`", 6, 3)
+        );
+        assert_eq!(
+            err("@type 'dev' | 'qa"),
+            error("unterminated string literal `'qa`", 14, 3)
+        );
+        assert_eq!(
+            err("@type 'dev' | "),
+            error(
+                "expected a literal, like 'a', \"a\", 1 or true, but found the end of the type hint",
+                14,
+                0
+            )
+        );
+        assert_eq!(err("@type"), error("expected a type after `@type`", 5, 0));
+        assert_eq!(
+            err("@type number extra"),
+            error("expected the end of the type hint but found `extra`", 13, 5)
+        );
+        assert_eq!(
+            err("@type 'a' 'b'"),
+            error("expected the end of the type hint but found `'b'`", 10, 3)
+        );
+        assert_eq!(err("@type |"), error("unexpected `|`", 6, 1));
+        assert_eq!(
+            err("@type number | 'a'"),
+            error("expected `undefined` but found `'a'`", 15, 3)
+        );
+        assert_eq!(err("@type ?"), error("unexpected `?`", 6, 1));
+        assert_eq!(
+            err(r#"@type 'a' | "b"#),
+            error(r#"unterminated string literal `"b`"#, 12, 2)
+        );
+        assert_eq!(
+            err("@type 1.2.3"),
+            error("expected the end of the type hint but found `.`", 9, 1)
+        );
+        assert_eq!(
+            err("@type int(min=1, step=2)"),
+            error("unknown constraint `step`, expected `min` or `max`", 17, 4)
+        );
+        assert_eq!(
+            err("@type number(10..1)"),
+            error(
+                "the range `(10..1)` is empty, its minimum is greater than its maximum",
+                12,
+                7
+            )
+        );
+        assert_eq!(
+            err("@type number(1-10)"),
+            error("expected `..` but found `-10`", 14, 3)
+        );
+        assert_eq!(
+            err("@type 'a' | 'b'[]"),
+            error("expected the end of the type hint but found `[`", 15, 1)
+        );
+        assert_eq!(
+            err("@type ('a' | 'b')"),
+            error("expected `[` but found the end of the type hint", 17, 0)
+        );
+        assert_eq!(
+            err("@type ('a' | undefined)[]"),
+            error(
+                "expected a literal, like 'a', \"a\", 1 or true, but found `undefined`",
+                13,
+                9
+            )
+        );
+        assert_eq!(
+            err("@type `https://${url}`"),
+            error(
+                "unknown placeholder `${url}`, expected `${string}`, `${number}` or `${boolean}`",
+                15,
+                6
+            )
+        );
+        assert_eq!(
+            err("@type `https://${string`"),
+            error(
+                "unknown placeholder `${string`, expected `${string}`, `${number}` or `${boolean}`",
+                15,
+                8
+            )
+        );
+        assert_eq!(
+            err("@type `https://"),
+            error("unterminated string literal `` `https:// ``", 6, 9)
+        );
+        assert_eq!(
+            err("@type date(utc)"),
+            error("unknown modifier `utc`, expected `iso`", 11, 3)
+        );
+        assert_eq!(
+            err("@type duration(weeks)"),
+            error(
+                "unknown unit `weeks` for duration, expected one of `ms`, `s`, `m`, `h`, `d`",
+                15,
+                5
+            )
+        );
+        assert_eq!(
+            err("@type bytes()"),
+            error("unknown unit `)` for bytes, expected one of `B`, `KB`, `MB`, `GB`, `TB`, `KiB`, `MiB`, `GiB`, `TiB`", 12, 1)
+        );
+        assert_eq!(
+            err("@type json<{ free: int }>"),
+            error("expected a JSON type, like `string`, `number`, `boolean`, a 'literal' or an object `{ key: number }`, but found `int`", 19, 3)
+        );
+        assert_eq!(
+            err("@type json<{ free number }>"),
+            error("expected `:` but found `number`", 18, 6)
+        );
+        assert_eq!(
+            err("@type json<{ 1: number }>"),
+            error(
+                "expected a field name, like `key` or 'key', but found `1`",
+                13,
+                1
+            )
+        );
+        assert_eq!(
+            err("@type json<{ free: number }"),
+            error("expected `>` but found the end of the type hint", 27, 0)
+        );
+        assert_eq!(
+            err("@type string[] sep=''"),
+            error("the separator of an array can't be empty", 19, 2)
+        );
+        assert_eq!(
+            err("@type port(1..10)"),
+            error("expected the end of the type hint but found `(`", 10, 1)
+        );
+        assert_eq!(
+            err("@type 'a' | string"),
+            error(
+                "expected a literal, like 'a', \"a\", 1 or true, but found `string`",
+                12,
+                6
+            )
+        );
+        assert_eq!(
+            err("@type ’qa’"),
+            error(
+                "unexpected `’`, string literals need straight single quotes: 'like this'",
+                6,
+                3
+            )
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn lexing_never_splits_a_character(source in any::<String>()) {
+            let mut end = 0;
+
+            for token in Lexer::new(&source) {
+                prop_assert!(token.position >= end);
+                prop_assert_eq!(source.get(token.position..token.position + token.text.len()), Some(token.text));
+                end = token.position + token.text.len();
+            }
+        }
+
+        #[test]
+        fn type_hint_errors_point_inside_the_source(source in "(# )?@type .*|.*") {
+            if let Err(e) = source.as_str().into_type_hint() {
+                prop_assert!(source.get(e.position..e.position + e.width).is_some());
+            }
+        }
     }
 }
@@ -0,0 +1,692 @@
+use std::{collections::BTreeMap, net::IpAddr};
+
+use regex::Regex;
+use serde_json::Value;
+
+use super::{
+    diagnostic::{Diagnostic, Severity},
+    infer::is_url,
+    merge::{merge_variables, OnConflict},
+    parse::Variable,
+    typehint_parser::{template_regex, Format, JsonShape, Literal, Quantity, Transform, TypeHint},
+    zod::Metadata,
+};
+
+fn parse_number(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+fn matches_literal(literal: &Literal, value: &str) -> bool {
+    match literal {
+        Literal::String(s) => s.as_ref() == value,
+        Literal::Number(n) => parse_number(value) == parse_number(n),
+        Literal::Boolean(b) => value == b.to_string(),
+    }
+}
+
+fn describe(format: Format) -> &'static str {
+    match format {
+        Format::Url => "a url",
+        Format::Email => "an email address",
+        Format::Uuid => "a uuid",
+        Format::Ip => "an ip address",
+        Format::Hostname => "a hostname",
+        Format::Base64 => "base64 data",
+    }
+}
+
+fn is_hostname(value: &str) -> bool {
+    value.len() <= 253
+        && value.split('.').all(|label| {
+            (1..=63).contains(&label.len())
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .bytes()
+                    .all(|c| c.is_ascii_alphanumeric() || c == b'-')
+        })
+}
+
+fn is_format(format: Format, value: &str) -> bool {
+    match format {
+        Format::Url => is_url(value),
+        Format::Email => value.split_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty()
+                && !local.contains(char::is_whitespace)
+                && domain.contains('.')
+                && is_hostname(domain)
+        }),
+        Format::Uuid => {
+            let groups = value.split('-').map(str::len).collect::<Vec<_>>();
+
+            groups == [8, 4, 4, 4, 12] && value.chars().all(|c| c == '-' || c.is_ascii_hexdigit())
+        }
+        Format::Ip => value.parse::<IpAddr>().is_ok(),
+        Format::Hostname => is_hostname(value),
+        Format::Base64 => {
+            let data = value.trim_end_matches('=');
+
+            value.len().is_multiple_of(4)
+                && value.len() - data.len() <= 2
+                && data
+                    .bytes()
+                    .all(|c| c.is_ascii_alphanumeric() || c == b'+' || c == b'/')
+        }
+    }
+}
+
+/// Parses exactly `len` digits.
+fn digits(s: &str, len: usize) -> Option<u32> {
+    (s.len() == len && s.bytes().all(|c| c.is_ascii_digit()))
+        .then(|| s.parse().ok())
+        .flatten()
+}
+
+/// Whether a value is a calendar date, like `2024-01-31`.
+fn is_date(value: &str) -> bool {
+    let mut parts = value.splitn(3, '-');
+    let (Some(year), Some(month), Some(day)) = (
+        parts.next().and_then(|s| digits(s, 4)),
+        parts.next().and_then(|s| digits(s, 2)),
+        parts.next().and_then(|s| digits(s, 2)),
+    ) else {
+        return false;
+    };
+
+    let leap = year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => 0,
+    };
+
+    (1..=days).contains(&day)
+}
+
+/// Whether a value is a timestamp, like `2024-01-31T09:00:00Z`. Strictly ISO 8601 timestamps, as
+/// zod checks them, have seconds and a time zone, otherwise anything a `Date` can be coerced from
+/// is fine, like `2024-01-31 09:00`.
+fn is_datetime(value: &str, iso: bool) -> bool {
+    let Some((date, time)) = value
+        .split_once('T')
+        .or_else(|| value.split_once(' ').filter(|_| !iso))
+    else {
+        return false;
+    };
+
+    let (time, zone) = match time.strip_suffix('Z') {
+        Some(time) => (time, Some(None)),
+        None => match time.rfind(['+', '-']) {
+            Some(i) => (&time[..i], Some(Some(&time[i + 1..]))),
+            None => (time, None),
+        },
+    };
+
+    let valid_zone = match zone {
+        None => !iso,
+        Some(None) => true,
+        Some(Some(offset)) => offset
+            .split_once(':')
+            .is_some_and(|(h, m)| digits(h, 2) <= Some(23) && digits(m, 2) <= Some(59)),
+    };
+
+    let (time, fraction) = time.split_once('.').unwrap_or((time, "0"));
+    let mut parts = time.split(':');
+    let (hours, minutes, seconds) = (parts.next(), parts.next(), parts.next());
+    let valid_time = hours.and_then(|h| digits(h, 2)).is_some_and(|h| h <= 23)
+        && minutes.and_then(|m| digits(m, 2)).is_some_and(|m| m <= 59)
+        && match seconds {
+            Some(s) => digits(s, 2).is_some_and(|s| s <= 59),
+            None => !iso && fraction == "0",
+        }
+        && parts.next().is_none()
+        && !fraction.is_empty()
+        && fraction.bytes().all(|c| c.is_ascii_digit());
+
+    is_date(date) && valid_zone && valid_time
+}
+
+/// Whether a value is an amount, optionally followed by a unit, e.g. `30s`, `1.5 GiB` or `100`.
+fn is_quantity(quantity: Quantity, value: &str) -> bool {
+    let value = value.trim();
+    let (amount, unit) = value.split_at(
+        value
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(value.len()),
+    );
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, "0"));
+    let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit());
+
+    all_digits(whole)
+        && all_digits(fraction)
+        && (unit.trim().is_empty() || quantity.unit(unit.trim()).is_some())
+}
+
+/// Decodes base64 data into UTF-8 text, as forgivingly as `atob`, i.e. ignoring whitespace and
+/// missing padding.
+fn decode_base64(value: &str) -> Option<String> {
+    let data = value
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect::<Vec<_>>();
+    let data = match data.len() % 4 {
+        0 if data.ends_with(b"==") => &data[..data.len() - 2],
+        0 if data.ends_with(b"=") => &data[..data.len() - 1],
+        _ => &data[..],
+    };
+
+    if data.len() % 4 == 1 {
+        return None;
+    }
+
+    let sextets = data
+        .iter()
+        .map(|&c| match c {
+            b'A'..=b'Z' => Some(c - b'A'),
+            b'a'..=b'z' => Some(c - b'a' + 26),
+            b'0'..=b'9' => Some(c - b'0' + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let bytes = sextets
+        .chunks(4)
+        .flat_map(|chunk| {
+            let bits = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |bits, (i, &s)| bits | u32::from(s) << (18 - 6 * i));
+
+            bits.to_be_bytes()[1..chunk.len()].to_vec()
+        })
+        .collect::<Vec<_>>();
+
+    String::from_utf8(bytes).ok()
+}
+
+/// Applies the transforms to a value, like the zod schema does, into the values its type hint is
+/// checked against: the value itself, or its parts when it's split.
+fn transform(value: &str, transforms: &[Transform]) -> Result<Vec<String>, String> {
+    let mut values = vec![value.to_string()];
+
+    for transform in transforms {
+        values = match transform {
+            Transform::Trim => values.iter().map(|v| v.trim().to_string()).collect(),
+            Transform::Lowercase => values.iter().map(|v| v.to_lowercase()).collect(),
+            Transform::Uppercase => values.iter().map(|v| v.to_uppercase()).collect(),
+            Transform::Base64 => values
+                .iter()
+                .map(|v| decode_base64(v))
+                .collect::<Option<_>>()
+                .ok_or("expected base64 encoded text")?,
+            Transform::Split(sep) => values
+                .iter()
+                .flat_map(|v| v.split(sep.as_ref()).map(str::to_string))
+                .collect(),
+        };
+    }
+
+    Ok(values)
+}
+
+fn matches_json_literal(literal: &Literal, value: &Value) -> bool {
+    match literal {
+        Literal::String(s) => value.as_str() == Some(s),
+        Literal::Number(n) => value.as_f64() == parse_number(n),
+        Literal::Boolean(b) => value.as_bool() == Some(*b),
+    }
+}
+
+/// Checks a parsed JSON value against its expected shape, `path` being where the value is in the
+/// document, e.g. `plans[0].price`.
+fn check_json(shape: &JsonShape, value: &Value, path: &str) -> Result<(), String> {
+    let mismatch = |expected: &str| match path {
+        "" => Err(format!("expected {expected}")),
+        path => Err(format!("expected {expected} at `{path}`")),
+    };
+
+    match shape {
+        JsonShape::Unknown => Ok(()),
+        JsonShape::String if !value.is_string() => mismatch("a string"),
+        JsonShape::Number if !value.is_number() => mismatch("a number"),
+        JsonShape::Boolean if !value.is_boolean() => mismatch("a boolean"),
+        JsonShape::String | JsonShape::Number | JsonShape::Boolean => Ok(()),
+        JsonShape::Union(literals) if !literals.iter().any(|l| matches_json_literal(l, value)) => {
+            mismatch(&format!("one of {shape}"))
+        }
+        JsonShape::Union(_) => Ok(()),
+        JsonShape::Array(element) => match value.as_array() {
+            Some(values) => values
+                .iter()
+                .enumerate()
+                .try_for_each(|(i, value)| check_json(element, value, &format!("{path}[{i}]"))),
+            None => mismatch("an array"),
+        },
+        JsonShape::Object(fields) => {
+            let Some(object) = value.as_object() else {
+                return mismatch("an object");
+            };
+
+            fields.iter().try_for_each(|field| {
+                let path = match path {
+                    "" => field.name.to_string(),
+                    path => format!("{path}.{}", field.name),
+                };
+
+                match object.get(field.name.as_ref()) {
+                    Some(value) => check_json(&field.shape, value, &path),
+                    None if field.optional => Ok(()),
+                    None => Err(format!("missing `{path}`")),
+                }
+            })
+        }
+    }
+}
+
+/// Checks a value against a type hint, describing what was expected if it doesn't match.
+fn check(th: &TypeHint, value: &str) -> Result<(), String> {
+    match th {
+        TypeHint::String => Ok(()),
+        TypeHint::Boolean => match value {
+            "true" | "false" => Ok(()),
+            _ => Err("expected `true` or `false`".to_string()),
+        },
+        TypeHint::Number { integer, min, max } => {
+            let Some(n) = parse_number(value) else {
+                return Err("expected a number".to_string());
+            };
+
+            if *integer && n.fract() != 0.0 {
+                return Err("expected an integer".to_string());
+            }
+            if let Some(min) = min.filter(|&min| n < min) {
+                return Err(format!("expected a number greater than or equal to {min}"));
+            }
+            if let Some(max) = max.filter(|&max| n > max) {
+                return Err(format!("expected a number less than or equal to {max}"));
+            }
+
+            Ok(())
+        }
+        TypeHint::Format(format) if !is_format(*format, value) => {
+            Err(format!("expected {}", describe(*format)))
+        }
+        TypeHint::Format(_) => Ok(()),
+        TypeHint::Union(literals) if !literals.iter().any(|l| matches_literal(l, value)) => {
+            Err(format!("expected one of {th}"))
+        }
+        TypeHint::Union(_) => Ok(()),
+        TypeHint::Custom(_, ty) => match ty.pattern.as_deref().map(Regex::new) {
+            Some(Ok(regex)) if !regex.is_match(value) => Err(format!("expected a {th}")),
+            _ => Ok(()),
+        },
+        TypeHint::Template(parts) => match Regex::new(&template_regex(parts)) {
+            Ok(regex) if !regex.is_match(value) => Err(format!("expected a match for {th}")),
+            _ => Ok(()),
+        },
+        TypeHint::Matching(th, pattern) => {
+            check(th, value)?;
+
+            match pattern.to_regex() {
+                Ok(regex) if !regex.is_match(value) => {
+                    Err(format!("expected a match for {pattern}"))
+                }
+                _ => Ok(()),
+            }
+        }
+        TypeHint::Array { element, separator } => value
+            .split(separator.as_ref())
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+            .try_for_each(|e| check(element, e).map_err(|reason| format!("{reason} in `{e}`"))),
+        TypeHint::Date { time: false, .. } if !is_date(value) => {
+            Err("expected a date, like `2024-01-31`".to_string())
+        }
+        TypeHint::Date { time: true, iso } if !is_datetime(value, *iso) => Err(match iso {
+            true => "expected an ISO 8601 timestamp, with seconds and a time zone, like `2024-01-31T09:00:00Z`",
+            false => "expected a timestamp, like `2024-01-31T09:00:00Z`",
+        }
+        .to_string()),
+        TypeHint::Date { .. } => Ok(()),
+        TypeHint::Quantity { quantity, .. } if !is_quantity(*quantity, value) => {
+            Err(format!("expected {}", quantity.describe()))
+        }
+        TypeHint::Quantity { .. } => Ok(()),
+        TypeHint::Json(shape) => {
+            let json = serde_json::from_str(value).map_err(|e| format!("invalid JSON, {e}"))?;
+
+            check_json(shape, &json, "")
+        }
+        TypeHint::Transformed(th, transforms) => {
+            let split = transforms.iter().any(|t| matches!(t, Transform::Split(_)));
+
+            transform(value, transforms)?.iter().try_for_each(|v| {
+                check(th, v).map_err(|reason| match split {
+                    true => format!("{reason} in `{v}`"),
+                    false => format!("{reason} once transformed into `{v}`"),
+                })
+            })
+        }
+        TypeHint::Optional(th) | TypeHint::Named(_, th) | TypeHint::Defaulted(th, _) => {
+            check(th, value)
+        }
+    }
+}
+
+/// Checks the `@default` of a variable against its own type hint. Unlike the values, a default
+/// ends up in the generated code, so one that doesn't match is an error.
+fn check_default(var: &Variable, meta: &Metadata) -> Option<Diagnostic> {
+    let (TypeHint::Defaulted(th, default), _) = var.type_hint.as_ref()? else {
+        return None;
+    };
+    let reason = check(th, default).err()?;
+    let annotation = var.annotations.iter().find(|a| a.tag() == "default")?;
+    let value_start = annotation.text.len()
+        - annotation.text[1..]
+            .trim_start_matches(|c: char| !c.is_whitespace())
+            .trim_start()
+            .len();
+
+    Some(Diagnostic {
+        severity: Severity::Error,
+        message: format!(
+            "the default for `{}` doesn't match its type hint `{th}`: {reason}, found `{default}`",
+            var.key
+        ),
+        meta: meta.clone(),
+        line: annotation.line,
+        column: annotation.column + value_start,
+        width: annotation.value().len(),
+    })
+}
+
+/// Checks the (non empty) values in the .env files against the type hint of their variable,
+/// once merged following `on_conflict`, like in the zod schema.
+/// These are only warnings, since example values and placeholders are common in .env files,
+/// but `@default`s that don't match their type hint are errors.
+pub fn validate_values(
+    variables: &[(Variable, Metadata)],
+    on_conflict: OnConflict,
+) -> Vec<Diagnostic> {
+    // conflicts are reported when generating, until then the first type hint is checked
+    let type_hints = merge_variables(variables, on_conflict)
+        .or_else(|_| merge_variables(variables, OnConflict::First))
+        .map(|merged| {
+            merged
+                .into_iter()
+                .filter_map(|(key, merged)| Some((key, merged.type_hint?)))
+                .collect::<BTreeMap<_, _>>()
+        })
+        .unwrap_or_default();
+
+    variables
+        .iter()
+        .filter(|(var, _)| !var.value.is_empty())
+        .filter_map(|(var, meta)| {
+            let th = type_hints.get(var.key.as_str())?;
+            let reason = check(th, &var.value).err()?;
+
+            Some(Diagnostic::at_text(
+                Severity::Warning,
+                format!(
+                    "invalid value for `{}`: {reason}, found `{}`",
+                    var.key, var.value
+                ),
+                meta,
+                var.span.start + var.key.len(),
+                &var.value,
+            ))
+        })
+        .chain(
+            variables
+                .iter()
+                .filter_map(|(var, meta)| check_default(var, meta)),
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::dotenv::{
+        merge::OnConflict, parse::parse_variables_with_type_hints, typehint_parser::TypeDefs,
+        validate::validate_values, zod::Metadata,
+    };
+
+    #[test]
+    fn validating_values_against_type_hints() {
+        let meta = Metadata::new(
+            "# @type port
+PORT=80.5
+# @type int(min=1)
+WORKERS=-3
+# @type number(0..1)
+RATIO=0.5
+# @type 'a' | 1 | true
+MIXED=1.0
+OTHER_MIXED=b # @type 'a' | 1 | true
+# @type int?
+EMPTY=
+# @type url
+API_URL=localhost:3000
+# @type email
+EMAIL=someone@example.com
+# @type uuid
+ID=0b5f8d2e-3c1a-4f6e-9a7b-2d4c6e8f0a1
+# @type ip
+HOST=::1
+# @type hostname
+OTHER_HOST=-example.com
+# @type base64
+SECRET=c2VjcmV0
+# @pattern /^[a-z]{2}-[a-z]+-\\d$/
+REGION=eu-west-1
+# @type string?
+# @pattern /^sk_live_[A-Za-z0-9]{4}$/
+STRIPE_KEY=sk_test_abcd
+# @type `redis://${string}:${number}`
+REDIS_URL=redis://localhost:port
+# @type int[] sep=';'
+IDS=1; 2;;3.5
+# @type date
+CUTOFF=2023-02-29
+# @type datetime
+WINDOW=2024-01-31 09:00
+# @type datetime(iso)
+OTHER_WINDOW=2024-01-31T09:00+01:00
+# @type duration(s)
+TIMEOUT=1.5 m
+# @type duration
+OTHER_TIMEOUT=5 minutes
+# @type bytes(MB)
+MAX_UPLOAD=1.GiB
+# @type json
+LIMITS={\"free\": 10,}
+# @type json<{ free: number; pro?: number; tiers: ('a' | 'b')[] }>
+PLANS={\"free\": 10, \"tiers\": [\"a\", \"c\"]}
+# @type json<{ free: number }>
+OTHER_PLANS={\"pro\": 10}
+# @type 'eu' | 'us'
+# @transform trim lowercase
+REGION_CODE=\" EU \"
+# @type int
+# @transform split(',') trim
+SHARDS=1, 2, x
+# @type url
+# @transform base64
+ENCODED_URL=aHR0cHM6Ly9leGFtcGxlLmNvbQ==
+# @transform base64
+OTHER_ENCODED=not base64!
+# @type 'a' | 'b'
+# @transform uppercase
+CASE=a
+# @type port
+# @default 'http'
+HTTP_PORT=
+# @type 'debug' | 'info'?
+# @default info
+LOG_LEVEL=
+# @type boolean
+DEBUG=yes
+# @type boolean
+# @default yes
+VERBOSE=
+",
+            Path::new(".env"),
+        );
+
+        let variables = parse_variables_with_type_hints(&meta, &TypeDefs::new(), &mut vec![])
+            .unwrap()
+            .into_iter()
+            .map(|var| (var, meta.clone()))
+            .collect::<Vec<_>>();
+
+        let diagnostics = validate_values(&variables, OnConflict::Error);
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| (d.message.as_str(), d.line, d.column, d.width))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "invalid value for `PORT`: expected an integer, found `80.5`",
+                    1,
+                    5,
+                    4
+                ),
+                (
+                    "invalid value for `WORKERS`: expected a number greater than or equal to 1, found `-3`",
+                    3,
+                    8,
+                    2
+                ),
+                (
+                    "invalid value for `OTHER_MIXED`: expected one of 'a' | 1 | true, found `b`",
+                    8,
+                    12,
+                    1
+                ),
+                (
+                    "invalid value for `API_URL`: expected a url, found `localhost:3000`",
+                    12,
+                    8,
+                    14
+                ),
+                (
+                    "invalid value for `ID`: expected a uuid, found `0b5f8d2e-3c1a-4f6e-9a7b-2d4c6e8f0a1`",
+                    16,
+                    3,
+                    35
+                ),
+                (
+                    "invalid value for `OTHER_HOST`: expected a hostname, found `-example.com`",
+                    20,
+                    11,
+                    12
+                ),
+                (
+                    "invalid value for `STRIPE_KEY`: expected a match for /^sk_live_[A-Za-z0-9]{4}$/, found `sk_test_abcd`",
+                    27,
+                    11,
+                    12
+                ),
+                (
+                    "invalid value for `REDIS_URL`: expected a match for `redis://${string}:${number}`, found `redis://localhost:port`",
+                    29,
+                    10,
+                    22
+                ),
+                (
+                    "invalid value for `IDS`: expected an integer in `3.5`, found `1; 2;;3.5`",
+                    31,
+                    4,
+                    9
+                ),
+                (
+                    "invalid value for `CUTOFF`: expected a date, like `2024-01-31`, found `2023-02-29`",
+                    33,
+                    7,
+                    10
+                ),
+                (
+                    "invalid value for `OTHER_WINDOW`: expected an ISO 8601 timestamp, with seconds and a time zone, like `2024-01-31T09:00:00Z`, found `2024-01-31T09:00+01:00`",
+                    37,
+                    13,
+                    22
+                ),
+                (
+                    "invalid value for `OTHER_TIMEOUT`: expected a duration, like `30s` or `5m`, found `5 minutes`",
+                    41,
+                    14,
+                    9
+                ),
+                (
+                    "invalid value for `MAX_UPLOAD`: expected a byte size, like `512MB` or `1GiB`, found `1.GiB`",
+                    43,
+                    11,
+                    5
+                ),
+                (
+                    "invalid value for `LIMITS`: invalid JSON, trailing comma at line 1 column 13, found `{\"free\": 10,}`",
+                    45,
+                    7,
+                    13
+                ),
+                (
+                    "invalid value for `PLANS`: expected one of 'a' | 'b' at `tiers[1]`, found `{\"free\": 10, \"tiers\": [\"a\", \"c\"]}`",
+                    47,
+                    6,
+                    33
+                ),
+                (
+                    "invalid value for `OTHER_PLANS`: missing `free`, found `{\"pro\": 10}`",
+                    49,
+                    12,
+                    11
+                ),
+                (
+                    "invalid value for `SHARDS`: expected a number in `x`, found `1, 2, x`",
+                    55,
+                    7,
+                    7
+                ),
+                (
+                    "invalid value for `OTHER_ENCODED`: expected base64 encoded text, found `not base64!`",
+                    60,
+                    14,
+                    11
+                ),
+                (
+                    "invalid value for `CASE`: expected one of 'a' | 'b' once transformed into `A`, found `a`",
+                    63,
+                    5,
+                    1
+                ),
+                (
+                    "invalid value for `DEBUG`: expected `true` or `false`, found `yes`",
+                    71,
+                    6,
+                    3
+                ),
+                (
+                    "the default for `HTTP_PORT` doesn't match its type hint `int(1..65535)`: expected a number, found `http`",
+                    65,
+                    11,
+                    6
+                ),
+                (
+                    "the default for `VERBOSE` doesn't match its type hint `boolean`: expected `true` or `false`, found `yes`",
+                    73,
+                    11,
+                    3
+                ),
+            ]
+        );
+    }
+}
@@ -5,7 +5,6 @@ use std::{
     fmt::Display,
     fs::File,
     io::{BufReader, BufWriter, Write},
-    ops::Deref,
     path::{Path, PathBuf},
     sync::Arc,
 };
@@ -15,8 +14,16 @@ use anyhow::{anyhow, Context, Result};
 use crate::command::prettify;
 
 use super::{
-    parse::{get_texts, parse_variables_with_type_hints, Variable},
-    typehint_parser::TypeHint,
+    infer::infer_type_hints,
+    jsdoc, load_variables,
+    merge::{merge_variables, MergedVariable},
+    parse::{descriptions, Variable},
+    process_env, property_name, read_variables,
+    requirement::{requirements, Requirement},
+    typehint_parser::{
+        escape_slashes, template_regex, Format, JsonShape, Literal, Transform, TypeHint,
+    },
+    Options,
 };
 
 use thiserror::Error;
@@ -31,145 +38,159 @@ pub enum ParseError {
 pub struct TypeHintAt {
     pub th: TypeHint,
     pub line: usize,
+    /// The line of the variable the type hint is documenting.
+    pub variable_line: usize,
     pub meta: Metadata,
 }
 
+impl TypeHintAt {
+    pub(super) fn new(meta: &Metadata, var: &Variable) -> Self {
+        let (th, line) = var
+            .type_hint
+            .clone()
+            .expect("should only point at a type hint that exists");
+
+        Self {
+            th,
+            line,
+            variable_line: var.line,
+            meta: meta.clone(),
+        }
+    }
+}
+
 impl Display for TypeHintAt {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut lines = self.meta.source.lines().skip(self.line);
-
-        let curr_line = lines
-            .next()
-            .expect("assumption that the type hint was parsed along with its line number, failed");
-        let next_line = lines
-            .next()
-            .expect("assumption that the type hint was parsed along with the variable it was decorating, failed");
+        let line = |n: usize| {
+            self.meta.source.lines().nth(n).expect(
+                "assumption that the type hint was parsed along with its line number, failed",
+            )
+        };
 
         writeln!(f, "{}", self.meta.path.to_string_lossy().dimmed())?;
-        writeln!(
+        write!(
             f,
             "  {}| {}",
             self.line + 1,
-            curr_line
+            line(self.line)
                 .replace(
                     self.th.to_string().as_str(),
                     self.th.to_string().green().to_string().as_str()
                 )
                 .bold()
         )?;
-        write!(f, "  {}| {}", self.line + 2, next_line)?;
 
-        Ok(())
-    }
-}
-
-impl From<(&Metadata, &(TypeHint, usize))> for TypeHintAt {
-    fn from(value: (&Metadata, &(TypeHint, usize))) -> Self {
-        Self {
-            th: value.1 .0.clone(),
-            meta: value.0.clone(),
-            line: value.1 .1,
+        // the type hint can be a trailing comment on the variable's line
+        if self.variable_line != self.line {
+            write!(
+                f,
+                "\n  {}| {}",
+                self.variable_line + 1,
+                line(self.variable_line)
+            )?;
         }
+
+        Ok(())
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Metadata {
-    source: Arc<str>,
-    path: Arc<Path>,
+    pub(super) source: Arc<str>,
+    pub(super) path: Arc<Path>,
 }
 
-pub fn generate_zod_schema(files: &[PathBuf], import_dotenv: bool) -> Result<String> {
-    let text_and_file_names = get_texts(files);
-
-    let sources = text_and_file_names.iter().map(|(source, path)| Metadata {
-        source: source.as_str().into(),
-        path: path.as_path().into(),
-    });
+impl Metadata {
+    pub fn new(source: &str, path: &Path) -> Self {
+        Self {
+            source: source.into(),
+            path: path.into(),
+        }
+    }
+}
 
-    generate_zod_schema_from_texts(sources, import_dotenv)
+pub fn generate_zod_schema(files: &[PathBuf], options: &Options) -> Result<String> {
+    generate_zod_schema_from_variables(&read_variables(files, options)?, options)
 }
 
 pub fn generate_zod_schema_from_texts(
     sources: impl Iterator<Item = Metadata>,
-    import_dotenv: bool,
+    options: &Options,
 ) -> Result<String> {
-    let mut map: BTreeMap<String, (Variable, Metadata)> = BTreeMap::new();
-
-    let variables = sources.flat_map(|meta| -> Vec<(Variable, Metadata)> {
-        let vars = parse_variables_with_type_hints(meta.source.deref())
-            .into_iter()
-            .map(|var| (var, meta.clone()))
-            .collect();
-
-        return vars;
-    });
-
-    for (var, meta) in variables {
-        if let Some((v, o_meta)) = map.get(&var.key) {
-            if let (Some(lt), Some(rt)) = (&v.type_hint, &var.type_hint) {
-                if lt.0 != rt.0 {
-                    return Err(ParseError::ConflictingTypes {
-                        a: (o_meta, lt).into(),
-                        b: (&meta, rt).into(),
-                    })
-                    .context(
-                        "found some conflicting types while parsing variables with type hints",
-                    );
-                }
-            }
-        }
+    generate_zod_schema_from_variables(&load_variables(sources, options)?, options)
+}
+
+pub fn generate_zod_schema_from_variables(
+    variables: &[(Variable, Metadata)],
+    options: &Options,
+) -> Result<String> {
+    let inferred = match options.infer {
+        Some(inference) => infer_type_hints(variables.iter().map(|(var, _)| var), inference),
+        None => BTreeMap::new(),
+    };
+
+    let vars = merge_variables(variables, options.on_conflict)?
+        .into_values()
+        .collect::<Vec<_>>();
 
-        let no_type_hint_or_variable_yet = map
-            .get(&var.key)
-            .map(|(var, _)| var.type_hint.is_none())
-            .unwrap_or(true);
+    let mut typedefs = Vec::new();
 
-        if no_type_hint_or_variable_yet {
-            map.insert(var.key.clone(), (var, meta));
+    for merged in &vars {
+        if let Some(th) = &merged.type_hint {
+            collect_typedefs(th, &mut typedefs);
         }
     }
 
-    let vars = map.into_values().collect::<Vec<_>>();
-
     let next_public_vars = vars
         .iter()
-        .filter(|&v| v.0.is_next_public())
+        .filter(|v| v.var.is_next_public())
         .collect::<Vec<_>>();
     let other_vars = vars
         .iter()
-        .filter(|v| !v.0.is_next_public())
+        .filter(|v| !v.var.is_next_public())
         .collect::<Vec<_>>();
 
-    let to_field_schema = |(var, meta): &&(Variable, Metadata)| -> String {
-        format!(
-            r#"    {}: {},"#,
-            var.key,
-            match &var.type_hint {
-                Some(th) => {
-                    let schema = match &th.0 {
-                        super::typehint_parser::TypeHint::String => "z.string()".to_string(),
-                        super::typehint_parser::TypeHint::Number => "z.coerce.number()".to_string(),
-                        super::typehint_parser::TypeHint::Boolean => {
-                            "z.coerce.boolean()".to_string()
-                        }
-                        super::typehint_parser::TypeHint::Union(values) => {
-                            format!("z.enum([{}])", values.join(","))
-                        }
-                    };
-
-                    // including comment for the file and the line from which
-                    // to find the type hint
-                    format!(
-                        r#"{} /* from {:?} on line {} */"#,
-                        schema,
-                        meta.path,
-                        th.1 + 1
-                    )
-                }
-                None => "z.string()".to_string(),
-            }
-        )
+    let requirements = requirements(variables, options.profiles)?;
+    let descriptions = descriptions(variables);
+
+    let to_field_schema = |merged: &&MergedVariable| -> String {
+        let var = merged.var;
+        let requirement = &requirements[var.key.as_str()];
+
+        let (th, comment) = match (&merged.type_hint, inferred.get(&var.key)) {
+            // including comment for the file(s) and the line(s) from which
+            // to find the type hint
+            (Some(th), _) => (Some(th), Some(merged.origin())),
+            (None, Some(th)) => (Some(th), Some("inferred".to_string())),
+            (None, None) => (None, None),
+        };
+
+        let mut schema = match th {
+            Some(TypeHint::Optional(th)) if *requirement == Requirement::Required => zod_schema(th),
+            Some(th) => zod_schema(th),
+            None => "z.string()".to_string(),
+        };
+
+        if *requirement == Requirement::Optional && !th.is_some_and(TypeHint::is_optional) {
+            schema.push_str(".optional()");
+        }
+        // described for the errors at runtime, and documented for the editor
+        let description = descriptions.get(var.key.as_str());
+        if let Some(description) = description {
+            schema.push_str(&format!(".describe({})", Value::from(*description)));
+        }
+        if let Some(comment) = comment {
+            schema = format!("{schema} /* {comment} */");
+        }
+        if let Requirement::InModes(modes) = requirement {
+            schema = format!("inModes({}, {schema})", Value::from(modes.clone()));
+        }
+
+        let doc = description
+            .map(|d| format!("{}\n", jsdoc(d, "    ")))
+            .unwrap_or_default();
+
+        format!(r#"{doc}    {}: {schema},"#, property_name(&var.key))
     };
 
     let js_code = include_str!("module.ts");
@@ -179,14 +200,16 @@ pub fn generate_zod_schema_from_texts(
         .next()
         .expect("should have an import line at the top of the js implementation");
 
-    let js_impl = js_code
-        .lines()
-        .skip_while(|line| !line.contains("/* --- MAIN IMPLEMENTATION BELOW --- */"))
-        .skip(1)
-        .collect::<Vec<_>>()
-        .join("\n");
+    let js_impl = main_implementation(js_code);
 
-    let dotenv_import = if import_dotenv {
+    let read_env_impl = if options.expand {
+        main_implementation(include_str!("expand.ts"))
+    } else {
+        "function readEnv(prop: string) {\n  return processEnv[prop as keyof typeof processEnv];\n}"
+            .to_string()
+    };
+
+    let dotenv_import = if options.import_dotenv {
         "import dotenv from \"dotenv\";\ndotenv.configDotenv();"
     } else {
         ""
@@ -196,7 +219,7 @@ pub fn generate_zod_schema_from_texts(
         r#"
 {js_import_line}
 {dotenv_import}
-
+{}
 const clientEnvSchemas = {{
 {}
 }}
@@ -208,10 +231,20 @@ const serverEnvSchemas = {{
 
 {js_impl}
 
+{read_env_impl}
+
 const processEnv = {{
 {}
 }}
                "#,
+        typedefs
+            .iter()
+            .map(|(name, th)| format!(
+                "\nexport const {name} = {};\nexport type {name} = z.infer<typeof {name}>;\n",
+                zod_schema(th)
+            ))
+            .collect::<Vec<_>>()
+            .join(""),
         next_public_vars
             .iter()
             .map(to_field_schema)
@@ -223,7 +256,10 @@ const processEnv = {{
             .collect::<Vec<_>>()
             .join("\n"),
         vars.iter()
-            .map(|var| format!("   {}: process.env.{},", var.0.key, var.0.key))
+            .map(|merged| {
+                let key = &merged.var.key;
+                format!("   {}: {},", property_name(key), process_env(key))
+            })
             .collect::<Vec<_>>()
             .join("\n"),
     );
@@ -231,6 +267,251 @@ const processEnv = {{
     Ok(output)
 }
 
+fn zod_schema(th: &TypeHint) -> String {
+    match th {
+        TypeHint::String => "z.string()".to_string(),
+        TypeHint::Number { integer, min, max } => {
+            let mut schema = "z.coerce.number()".to_string();
+
+            if *integer {
+                schema.push_str(".int()");
+            }
+            if let Some(min) = min {
+                schema.push_str(&format!(".min({min})"));
+            }
+            if let Some(max) = max {
+                schema.push_str(&format!(".max({max})"));
+            }
+
+            schema
+        }
+        // strict, as coercing would make `false` true
+        TypeHint::Boolean => "z.enum(['true','false']).transform((v) => v === 'true')".to_string(),
+        TypeHint::Format(format) => format!(
+            "z.string(){}",
+            match format {
+                Format::Url => ".url()",
+                Format::Email => ".email()",
+                Format::Uuid => ".uuid()",
+                Format::Ip => ".ip()",
+                Format::Hostname => {
+                    r".regex(/^(?=.{1,253}$)[a-z0-9]([a-z0-9-]{0,61}[a-z0-9])?(\.[a-z0-9]([a-z0-9-]{0,61}[a-z0-9])?)*$/i)"
+                }
+                Format::Base64 => {
+                    r".regex(/^([A-Za-z0-9+/]{4})*([A-Za-z0-9+/]{2}==|[A-Za-z0-9+/]{3}=)?$/)"
+                }
+            }
+        ),
+        TypeHint::Union(values) if values.iter().all(|v| matches!(v, Literal::String(_))) => {
+            format!(
+                "z.enum([{}])",
+                values
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            )
+        }
+        TypeHint::Union(values) => match &values[..] {
+            [literal] => literal_schema(literal),
+            _ => format!(
+                "z.union([{}])",
+                values
+                    .iter()
+                    .map(literal_schema)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        },
+        TypeHint::Matching(th, pattern) => format!(
+            "{}.regex(/{}/{})",
+            zod_schema(th),
+            escape_slashes(&pattern.source),
+            pattern.flags
+        ),
+        TypeHint::Template(parts) => format!(
+            "z.string().regex(/{}/)",
+            template_regex(parts).replace('/', "\\/")
+        ),
+        TypeHint::Array { element, separator } => format!(
+            "z.string().transform((s) => s.split({}).map((e) => e.trim()).filter((e) => e !== \"\")).pipe(z.array({}))",
+            Value::String(separator.to_string()),
+            zod_schema(element)
+        ),
+        TypeHint::Date { iso: false, .. } => "z.coerce.date()".to_string(),
+        TypeHint::Date { time: false, .. } => r"z.string().regex(/^\d{4}-\d{2}-\d{2}$/)".to_string(),
+        TypeHint::Date { time: true, .. } => "z.string().datetime({ offset: true })".to_string(),
+        TypeHint::Quantity { quantity, unit } => {
+            format!("{}({})", quantity.name(), Value::String(unit.to_string()))
+        }
+        TypeHint::Json(shape) => format!(
+            "z.string().transform((s, ctx) => {{ try {{ return JSON.parse(s) }} catch {{ ctx.addIssue({{ code: z.ZodIssueCode.custom, message: \"Invalid JSON\" }}); return z.NEVER }} }}).pipe({})",
+            json_schema(shape)
+        ),
+        TypeHint::Transformed(th, transforms) => {
+            let (before, after) = match transforms
+                .iter()
+                .position(|t| matches!(t, Transform::Split(_)))
+            {
+                Some(i) => (&transforms[..=i], &transforms[i + 1..]),
+                None => (&transforms[..], &[][..]),
+            };
+
+            let mut expression = transform_expression("s", before);
+            let mut schema = zod_schema(th);
+
+            if let Some(Transform::Split(_)) = before.last() {
+                if !after.is_empty() {
+                    expression = format!(
+                        "{expression}.map((e) => {})",
+                        transform_expression("e", after)
+                    );
+                }
+                schema = format!("z.array({schema})");
+            }
+
+            match transforms.contains(&Transform::Base64) {
+                true => format!(
+                    "z.string().transform((s, ctx) => {{ try {{ return {expression} }} catch {{ ctx.addIssue({{ code: z.ZodIssueCode.custom, message: \"Invalid base64\" }}); return z.NEVER }} }}).pipe({schema})"
+                ),
+                false => format!("z.string().transform((s) => {expression}).pipe({schema})"),
+            }
+        }
+        TypeHint::Optional(th) => format!("{}.optional()", zod_schema(th)),
+        TypeHint::Defaulted(th, default) => format!(
+            "{}.default({})",
+            zod_schema(th),
+            default_input(th, default)
+        ),
+        TypeHint::Named(name, _) => name.to_string(),
+        TypeHint::Custom(_, ty) => match (&ty.zod, &ty.pattern) {
+            (Some(zod), _) => zod.clone(),
+            (None, Some(pattern)) => format!("z.string().regex(/{}/)", escape_slashes(pattern)),
+            (None, None) => "z.string()".to_string(),
+        },
+    }
+}
+
+/// The default as an input of the schema, which isn't always a string, e.g. `z.coerce.number()`
+/// takes numbers.
+fn default_input(th: &TypeHint, default: &str) -> String {
+    let string = || Value::String(default.to_string()).to_string();
+
+    match th {
+        TypeHint::Number { .. } => default.trim().to_string(),
+        TypeHint::Date { iso: false, .. } => format!("new Date({})", string()),
+        TypeHint::Union(literals) => match literals.iter().find(|l| match l {
+            Literal::Number(n) => n.parse::<f64>().ok() == default.trim().parse::<f64>().ok(),
+            _ => false,
+        }) {
+            Some(number) => js_literal(number),
+            None => string(),
+        },
+        TypeHint::Optional(th) | TypeHint::Named(_, th) => default_input(th, default),
+        _ => string(),
+    }
+}
+
+/// The javascript expression applying the transforms to the string in `variable`.
+fn transform_expression(variable: &str, transforms: &[Transform]) -> String {
+    transforms.iter().fold(
+        variable.to_string(),
+        |expression, transform| match transform {
+            Transform::Trim => format!("{expression}.trim()"),
+            Transform::Lowercase => format!("{expression}.toLowerCase()"),
+            Transform::Uppercase => format!("{expression}.toUpperCase()"),
+            Transform::Base64 => format!("decodeBase64({expression})"),
+            Transform::Split(sep) => {
+                format!("{expression}.split({})", Value::String(sep.to_string()))
+            }
+        },
+    )
+}
+
+/// Collects the `@typedef`s a type hint refers to, after the ones they refer to themselves, so that
+/// each can be declared after its dependencies.
+fn collect_typedefs<'th>(th: &'th TypeHint, typedefs: &mut Vec<(&'th str, &'th TypeHint)>) {
+    match th {
+        TypeHint::Named(name, th) => {
+            collect_typedefs(th, typedefs);
+
+            if !typedefs.iter().any(|(n, _)| n == &name.as_ref()) {
+                typedefs.push((name, th));
+            }
+        }
+        TypeHint::Optional(th)
+        | TypeHint::Defaulted(th, _)
+        | TypeHint::Matching(th, _)
+        | TypeHint::Transformed(th, _)
+        | TypeHint::Array { element: th, .. } => collect_typedefs(th, typedefs),
+        _ => {}
+    }
+}
+
+/// Unlike the env, parsed JSON holds actual numbers and booleans, so nothing is coerced.
+fn json_schema(shape: &JsonShape) -> String {
+    match shape {
+        JsonShape::Unknown => "z.unknown()".to_string(),
+        JsonShape::String => "z.string()".to_string(),
+        JsonShape::Number => "z.number()".to_string(),
+        JsonShape::Boolean => "z.boolean()".to_string(),
+        JsonShape::Union(values) => match &values[..] {
+            [literal] => format!("z.literal({})", js_literal(literal)),
+            _ => format!(
+                "z.union([{}])",
+                values
+                    .iter()
+                    .map(|literal| format!("z.literal({})", js_literal(literal)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        },
+        JsonShape::Array(element) => format!("z.array({})", json_schema(element)),
+        JsonShape::Object(fields) => format!(
+            "z.object({{ {} }})",
+            fields
+                .iter()
+                .map(|field| format!(
+                    "{}: {}{}",
+                    Value::String(field.name.to_string()),
+                    json_schema(&field.shape),
+                    if field.optional { ".optional()" } else { "" }
+                ))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// The literal in JavaScript, where a number is normalized, e.g. `7` for `007`, which would be a
+/// legacy octal literal.
+fn js_literal(literal: &Literal) -> String {
+    match literal {
+        Literal::Number(n) => n
+            .parse::<f64>()
+            .map_or_else(|_| n.to_string(), |n| n.to_string()),
+        literal => literal.to_string(),
+    }
+}
+
+/// The env only holds strings, so non string literals are matched by what they're coerced to.
+fn literal_schema(literal: &Literal) -> String {
+    match literal {
+        Literal::String(_) => format!("z.literal({literal})"),
+        Literal::Number(_) => format!("z.coerce.number().pipe(z.literal({}))", js_literal(literal)),
+        Literal::Boolean(b) => format!("z.literal('{b}').transform(() => {b} as const)"),
+    }
+}
+
+fn main_implementation(js_code: &str) -> String {
+    js_code
+        .lines()
+        .skip_while(|line| !line.contains("/* --- MAIN IMPLEMENTATION BELOW --- */"))
+        .skip(1)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 pub fn add_tsconfig_path<P: AsRef<Path>>(path: P) -> Result<()> {
     let mut ts_config: Value = File::open("./tsconfig.json")
         .context("couldn't open tsconfig.json")
@@ -277,8 +558,17 @@ mod tests {
     use std::path::{Path, PathBuf};
 
     use insta::{assert_debug_snapshot, assert_display_snapshot};
+    use proptest::prelude::*;
+    use regex::Regex;
 
-    use crate::dotenv::zod::{generate_zod_schema, generate_zod_schema_from_texts};
+    use crate::dotenv::{
+        config::Config,
+        infer::Inference,
+        merge::OnConflict,
+        typehint_parser::RESERVED_NAMES,
+        zod::{generate_zod_schema, generate_zod_schema_from_texts, Metadata},
+        Options,
+    };
 
     #[test]
     fn zod_schema_gen() {
@@ -287,7 +577,7 @@ mod tests {
                 PathBuf::from("src/dotenv/.env.test"),
                 PathBuf::from("src/dotenv/.env.test2"),
             ],
-            false,
+            &Options::default(),
         )
         .unwrap();
         assert_display_snapshot!(output);
@@ -313,7 +603,7 @@ KEY=
                 }
             });
 
-            generate_zod_schema_from_texts(sources, false)
+            generate_zod_schema_from_texts(sources, &Options::default())
         }
 
         fn gen_err(sources: &[String]) {
@@ -331,4 +621,570 @@ KEY=
         // This is not a conflict
         generate(&[case("string"), case("string")]).unwrap();
     }
+
+    #[test]
+    fn zod_schema_gen_with_merged_type_hints() {
+        let sources = [
+            "# @type 'dev' | 'qa'\nSTAGE=dev\n# @type int\nPORT=3000\n",
+            "# @type 'prod'\nSTAGE=prod\n# @type port\nPORT=80\n",
+        ];
+        let options = |on_conflict| Options {
+            on_conflict,
+            ..Default::default()
+        };
+
+        assert!(generate_from(&sources, &options(OnConflict::Union)).is_err());
+
+        let output = generate_from(&sources[..1], &options(OnConflict::Union)).unwrap();
+        assert!(output.contains(r#"    STAGE: z.enum(['dev','qa']) /* from ".env.0" on line 1 */"#));
+
+        let sources = [sources[0], "# @type 'prod'\nSTAGE=prod\n"];
+        let output = generate_from(&sources, &options(OnConflict::Union)).unwrap();
+        assert!(output.contains(
+            r#"    STAGE: z.enum(['dev','qa','prod']) /* merged from ".env.0" on line 1, ".env.1" on line 1 */"#
+        ));
+
+        let sources = ["# @type int\nPORT=3000\n", "# @type port\nPORT=80\n"];
+        let output = generate_from(&sources, &options(OnConflict::Last)).unwrap();
+        assert!(output.contains(
+            r#"    PORT: z.coerce.number().int().min(1).max(65535) /* from ".env.1" on line 1 */"#
+        ));
+        let output = generate_from(&sources, &options(OnConflict::First)).unwrap();
+        assert!(
+            output.contains(r#"    PORT: z.coerce.number().int() /* from ".env.0" on line 1 */"#)
+        );
+    }
+
+    #[test]
+    fn zod_schema_gen_checks_values_against_merged_type_hints() {
+        let options = |on_conflict| Options {
+            on_conflict,
+            deny_warnings: true,
+            ..Default::default()
+        };
+
+        let sources = ["# @type 'dev'\nSTAGE=dev\n", "# @type 'prod'\nSTAGE=prod\n"];
+        assert!(generate_from(&sources, &options(OnConflict::Union)).is_ok());
+
+        let sources = [
+            "# @type int\nAPI=3000\n",
+            "# @type url\nAPI=https://example.com\n",
+        ];
+        assert!(generate_from(&sources, &options(OnConflict::Last)).is_err());
+
+        let sources = [
+            "# @type int\nAPI=https://example.com\n",
+            "# @type url\nAPI=https://example.org\n",
+        ];
+        assert!(generate_from(&sources, &options(OnConflict::Last)).is_ok());
+        assert!(generate_from(&sources, &options(OnConflict::First)).is_err());
+    }
+
+    #[test]
+    fn zod_schema_gen_with_profiles() {
+        let sources = [
+            (".env", "# @type port\nPORT=3000\n"),
+            (".env.local", "SECRET=dev-secret\n"),
+            (".env.development", "# @type boolean\nDEBUG=true\n"),
+        ]
+        .map(|(path, source)| Metadata::new(source, Path::new(path)));
+        let options = Options {
+            profiles: true,
+            ..Default::default()
+        };
+
+        let output = generate_zod_schema_from_texts(sources.into_iter(), &options).unwrap();
+
+        assert!(output.contains(
+            r#"    PORT: z.coerce.number().int().min(1).max(65535) /* from ".env" on line 1 */,"#
+        ));
+        assert!(output.contains(
+            r#"    DEBUG: inModes(["development"], z.enum(['true','false']).transform((v) => v === 'true') /* from ".env.development" on line 1 */),"#
+        ));
+        assert!(
+            output.contains(r#"    SECRET: inModes(["development","production"], z.string()),"#)
+        );
+        assert!(
+            output.contains("function inModes<T extends ZodTypeAny>(modes: string[], schema: T) {")
+        );
+    }
+
+    #[test]
+    fn zod_schema_gen_with_requirements() {
+        let sources = [
+            (
+                ".env.development",
+                "PORT=3000\n# @type boolean\nDEBUG=true\nNAME=dev\n",
+            ),
+            (".env.production", "PORT=80\n# @required\nNAME=prod\n"),
+            (".env.example", "PORT=\nSENTRY_DSN=\n"),
+        ]
+        .map(|(path, source)| Metadata::new(source, Path::new(path)));
+
+        let output =
+            generate_zod_schema_from_texts(sources.into_iter(), &Options::default()).unwrap();
+
+        assert!(output.contains("    PORT: z.string(),"));
+        assert!(output.contains(
+            r#"    DEBUG: z.enum(['true','false']).transform((v) => v === 'true').optional() /* from ".env.development" on line 2 */,"#
+        ));
+        assert!(output.contains("    NAME: z.string(),"));
+        assert!(output.contains("    SENTRY_DSN: z.string().optional(),"));
+    }
+
+    #[test]
+    fn zod_schema_gen_with_descriptions() {
+        let output = generate_from(
+            &[
+                "# The port to listen on.\n# @type port\nPORT=3000\n",
+                "PORT=80\n# Where errors are reported,\n# if anywhere.\nSENTRY_DSN=\n",
+            ],
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(output.contains(
+            r#"    /** The port to listen on. */
+    PORT: z.coerce.number().int().min(1).max(65535).describe("The port to listen on.") /* from ".env.0" on line 2 */,"#
+        ));
+        assert!(output.contains(
+            r#"    /**
+     * Where errors are reported,
+     * if anywhere.
+     */
+    SENTRY_DSN: z.string().optional().describe("Where errors are reported,\nif anywhere."),"#
+        ));
+        assert!(output.contains("[`failed to read ${prop} from proccess.env`, description]"));
+    }
+
+    fn generate_from(sources: &[&str], options: &Options) -> Result<String, anyhow::Error> {
+        let sources = sources
+            .iter()
+            .enumerate()
+            .map(|(i, source)| Metadata::new(source, Path::new(&format!(".env.{}", i))));
+
+        generate_zod_schema_from_texts(sources, options)
+    }
+
+    #[test]
+    fn zod_schema_gen_with_inference() {
+        let sources = [
+            "PORT=3000\nDEBUG=true\nAPI_URL=https://example.com\nSTAGE=qa\nNAME=ntro\n",
+            "PORT=3001\nDEBUG=yes\nAPI_URL=http://localhost\nSTAGE=production\n# @type string\nNAME=other\n",
+        ];
+
+        let output = generate_from(
+            &sources,
+            &Options {
+                infer: Some(Inference::Aggressive),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert!(output.contains("    PORT: z.coerce.number() /* inferred */,"));
+        assert!(output.contains("    DEBUG: z.enum(['true','yes']) /* inferred */,"));
+        assert!(output.contains("    API_URL: z.string().url() /* inferred */,"));
+        assert!(output.contains("    STAGE: z.enum(['qa','production']) /* inferred */,"));
+        assert!(output.contains("    NAME: z.string() /* from \".env.1\" on line 5 */,"));
+
+        let output = generate_from(
+            &sources,
+            &Options {
+                infer: Some(Inference::Conservative),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert!(output.contains("    PORT: z.coerce.number() /* inferred */,"));
+        assert!(output.contains("    DEBUG: z.string(),"));
+        assert!(output.contains("    API_URL: z.string(),"));
+
+        let output = generate_from(&sources, &Options::default()).unwrap();
+
+        assert!(output.contains("    PORT: z.string(),"));
+
+        let output = generate_from(
+            &["DEBUG=false\n", "DEBUG=true\n"],
+            &Options {
+                infer: Some(Inference::Conservative),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert!(output.contains(
+            "    DEBUG: z.union([z.literal('true').transform(() => true as const), z.literal('false').transform(() => false as const)]) /* inferred */,"
+        ));
+    }
+
+    #[test]
+    fn reserving_the_names_of_the_module() {
+        let declaration = Regex::new(
+            r"(?m)^(?:export )?(?:const|let|function|class|type) (\w+)|^import (\w+), \{ (\w+) \}",
+        )
+        .unwrap();
+        let sources = [include_str!("module.ts"), include_str!("expand.ts")];
+
+        for source in sources {
+            for captures in declaration.captures_iter(source) {
+                for name in captures.iter().skip(1).flatten() {
+                    assert!(
+                        RESERVED_NAMES.contains(&name.as_str()),
+                        "`{}` isn't reserved",
+                        name.as_str()
+                    );
+                }
+            }
+        }
+
+        let output = generate_from(
+            &["# @typedef Env 'qa' | 'prod'\n# @type Env\nSTAGE=qa\n"],
+            &Options::default(),
+        )
+        .unwrap();
+        assert!(!output.contains("export type Env"));
+    }
+
+    #[test]
+    fn zod_schema_gen_with_quoted_keys() {
+        let output = generate_from(
+            &["APP.NAME=ntro\nmy-key=1\n2FA=on\nPORT=80\n"],
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(output.contains(r#"    "APP.NAME": z.string(),"#));
+        assert!(output.contains(r#"    "my-key": z.string(),"#));
+        assert!(output.contains(r#"   "APP.NAME": process.env["APP.NAME"],"#));
+        assert!(output.contains(r#"   "2FA": process.env["2FA"],"#));
+        assert!(output.contains("   PORT: process.env.PORT,"));
+    }
+
+    #[test]
+    fn zod_schema_gen_with_literal_unions() {
+        let output = generate_from(
+            &["# @type 1 | 2 | 3\nWORKERS=1\n\
+               # @type \"a\" | 'b'\nLETTER=a\n\
+               # @type 'auto' | false\nCACHE=auto\n\
+               # @type 8080\nPORT=8080\n\
+               # @type 007\nAGENT=7\n\
+               # @type 1.50 | 2\nRATIO=1.50\n"],
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(output.contains(
+            "    WORKERS: z.union([z.coerce.number().pipe(z.literal(1)), z.coerce.number().pipe(z.literal(2)), z.coerce.number().pipe(z.literal(3))])"
+        ));
+        assert!(output.contains("    LETTER: z.enum(['a','b'])"));
+        assert!(output.contains(
+            "    CACHE: z.union([z.literal('auto'), z.literal('false').transform(() => false as const)])"
+        ));
+        assert!(output.contains("    PORT: z.coerce.number().pipe(z.literal(8080))"));
+        assert!(output.contains("    AGENT: z.coerce.number().pipe(z.literal(7))"));
+        assert!(output.contains(
+            "    RATIO: z.union([z.coerce.number().pipe(z.literal(1.5)), z.coerce.number().pipe(z.literal(2))])"
+        ));
+    }
+
+    #[test]
+    fn zod_schema_gen_with_number_constraints() {
+        let output = generate_from(
+            &["# @type port\nPORT=8080\n\
+               # @type int(min=1)\nWORKERS=4\n\
+               # @type number(0..1)?\nRATIO=0.5\n"],
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(output.contains("    PORT: z.coerce.number().int().min(1).max(65535) /*"));
+        assert!(output.contains("    WORKERS: z.coerce.number().int().min(1) /*"));
+        assert!(output.contains("    RATIO: z.coerce.number().min(0).max(1).optional() /*"));
+    }
+
+    #[test]
+    fn zod_schema_gen_with_string_formats() {
+        let output = generate_from(
+            &["# @type email\nADMIN=a@b.co\n# @type ip\nHOST=127.0.0.1\n"],
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(output.contains("    ADMIN: z.string().email() /*"));
+        assert!(output.contains("    HOST: z.string().ip() /*"));
+    }
+
+    #[test]
+    fn zod_schema_gen_with_patterns() {
+        let output = generate_from(
+            &["# @pattern /^sk_live_[A-Za-z0-9]{24}$/\nSTRIPE_KEY=\n\
+               # @type email?\n# @pattern /@example\\.com$/i\nADMIN=\n\
+               # @pattern /^https:\\/\\/[a-z]+\\.com/api$/\nAPI_URL=\n"],
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(output.contains("    STRIPE_KEY: z.string().regex(/^sk_live_[A-Za-z0-9]{24}$/) /*"));
+        assert!(output
+            .contains("    ADMIN: z.string().email().regex(/@example\\.com$/i).optional() /*"));
+        assert!(
+            output.contains(r"    API_URL: z.string().regex(/^https:\/\/[a-z]+\.com\/api$/) /*")
+        );
+    }
+
+    #[test]
+    fn zod_schema_gen_with_transforms() {
+        let output = generate_from(
+            &[
+                "# @type 'eu' | 'us'\n# @transform trim lowercase\nREGION=eu\n\
+               # @type int\n# @transform split(',') trim\nSHARDS=1,2\n\
+               # @transform base64 split(':')\nCREDENTIALS=\n",
+            ],
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(output.contains(
+            r#"    REGION: z.string().transform((s) => s.trim().toLowerCase()).pipe(z.enum(['eu','us'])) /*"#
+        ));
+        assert!(output.contains(
+            r#"    SHARDS: z.string().transform((s) => s.split(",").map((e) => e.trim())).pipe(z.array(z.coerce.number().int())) /*"#
+        ));
+        assert!(output.contains(
+            r#"    CREDENTIALS: z.string().transform((s, ctx) => { try { return decodeBase64(s).split(":") } catch { ctx.addIssue({ code: z.ZodIssueCode.custom, message: "Invalid base64" }); return z.NEVER } }).pipe(z.array(z.string())) /*"#
+        ));
+        assert!(output.contains("function decodeBase64(data: string) {"));
+    }
+
+    #[test]
+    fn zod_schema_gen_with_booleans() {
+        let output = generate_from(
+            &["# @type boolean\nDEBUG=false\n# @type boolean[]\nFLAGS=true,false\n"],
+            &Options {
+                deny_warnings: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // `false` is only true once coerced, so it's matched as the string it is
+        assert!(output
+            .contains("    DEBUG: z.enum(['true','false']).transform((v) => v === 'true') /*"));
+        assert!(output
+            .contains("pipe(z.array(z.enum(['true','false']).transform((v) => v === 'true'))) /*"));
+        assert!(!output.contains("z.coerce.boolean()"));
+    }
+
+    #[test]
+    fn zod_schema_gen_with_defaults() {
+        let output = generate_from(
+            &["# @type port?\n# @default 3000\nPORT=\n\
+               # @type 'debug' | 'info' | 1\n# @default 1\nLOG_LEVEL=\n\
+               # @type boolean\n# @default false\nDEBUG=\n\
+               # @type duration(s)\n# @default 5m\nTIMEOUT=\n\
+               # @default 'eu-west-1'\nREGION=\n"],
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(
+            output.contains("    PORT: z.coerce.number().int().min(1).max(65535).default(3000) /*")
+        );
+        assert!(output.contains(
+            "    LOG_LEVEL: z.union([z.literal('debug'), z.literal('info'), z.coerce.number().pipe(z.literal(1))]).default(1) /*"
+        ));
+        assert!(output.contains(
+            r#"    DEBUG: z.enum(['true','false']).transform((v) => v === 'true').default("false") /*"#
+        ));
+        assert!(output.contains(r#"    TIMEOUT: duration("s").default("5m") /*"#));
+        assert!(output.contains(r#"    REGION: z.string().default("eu-west-1") /*"#));
+
+        let err = generate_from(
+            &["# @type port\n# @default 'http'\nPORT=\n"],
+            &Options::default(),
+        )
+        .unwrap_err();
+
+        assert!(format!("{err:?}").contains(
+            "the default for `PORT` doesn't match its type hint `int(1..65535)`: expected a number, found `http`"
+        ));
+
+        let err = generate_from(
+            &["# @type boolean\n# @default yes\nDEBUG=\n"],
+            &Options::default(),
+        )
+        .unwrap_err();
+
+        assert!(format!("{err:?}").contains(
+            "the default for `DEBUG` doesn't match its type hint `boolean`: expected `true` or `false`, found `yes`"
+        ));
+    }
+
+    #[test]
+    fn zod_schema_gen_with_arrays() {
+        let output = generate_from(
+            &["# @type string[]\nORIGINS=a.com,b.com\n\
+               # @type ('x' | 'y')[] sep=';'\nFLAGS=x;y\n"],
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(output.contains(
+            r#"    ORIGINS: z.string().transform((s) => s.split(",").map((e) => e.trim()).filter((e) => e !== "")).pipe(z.array(z.string())) /*"#
+        ));
+        assert!(output.contains(
+            r#"    FLAGS: z.string().transform((s) => s.split(";").map((e) => e.trim()).filter((e) => e !== "")).pipe(z.array(z.enum(['x','y']))) /*"#
+        ));
+    }
+
+    #[test]
+    fn zod_schema_gen_with_json() {
+        let output = generate_from(
+            &[
+                "# @type json<{ free: number; 'pro-plan'?: ('monthly' | 'yearly')[] }>
+PLANS={\"free\":0}
+\
+               # @type json?
+EXTRA=\n",
+            ],
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(output.contains(
+            r#"    PLANS: z.string().transform((s, ctx) => { try { return JSON.parse(s) } catch { ctx.addIssue({ code: z.ZodIssueCode.custom, message: "Invalid JSON" }); return z.NEVER } }).pipe(z.object({ "free": z.number(), "pro-plan": z.array(z.union([z.literal('monthly'), z.literal('yearly')])).optional() })) /*"#
+        ));
+        assert!(output.contains(".pipe(z.unknown()).optional() /*"));
+    }
+
+    #[test]
+    fn zod_schema_gen_with_template_literals() {
+        let output = generate_from(
+            &["# @type `https://${string}`\nAPI_URL=https://example.com\n\
+               # @type `redis://${string}:${number}`?\nREDIS_URL=\n"],
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(output.contains(r"    API_URL: z.string().regex(/^https:\/\/.*$/) /*"));
+        assert!(output.contains(
+            r"    REDIS_URL: z.string().regex(/^redis:\/\/.*:-?\d+(\.\d+)?$/).optional() /*"
+        ));
+    }
+
+    #[test]
+    fn zod_schema_gen_with_custom_types() {
+        let options = Options {
+            config: Config::parse(
+                r#"{
+                    "types": {
+                        "semver": { "pattern": "^\\d+\\.\\d+\\.\\d+$" },
+                        "awsRegion": { "zod": "z.enum(['us-east-1', 'eu-west-1'])" },
+                        "slug": {}
+                    }
+                }"#,
+            )
+            .unwrap(),
+            ..Default::default()
+        };
+
+        let output = generate_from(
+            &["# @type semver\nVERSION=1.2.3\n\
+               # @type awsRegion?\nREGION=\n\
+               # @typedef Slugs slug[]\n# @type Slugs\nSLUGS=a,b\n"],
+            &options,
+        )
+        .unwrap();
+
+        assert!(output.contains(r"    VERSION: z.string().regex(/^\d+\.\d+\.\d+$/) /*"));
+        assert!(output.contains("    REGION: z.enum(['us-east-1', 'eu-west-1']).optional() /*"));
+        assert!(output.contains(".pipe(z.array(z.string()));\nexport type Slugs"));
+
+        let err = generate_from(&["# @typedef semver string\n"], &options).unwrap_err();
+
+        assert!(format!("{err:?}").contains("the type `semver` is already defined in the config"));
+
+        let err = generate_from(
+            &["# @type semver\nVERSION=1.2\n"],
+            &Options {
+                deny_warnings: true,
+                ..options
+            },
+        )
+        .unwrap_err();
+
+        assert!(format!("{err:?}")
+            .contains("invalid value for `VERSION`: expected a semver, found `1.2`"));
+    }
+
+    #[test]
+    fn zod_schema_gen_with_typedefs() {
+        let output = generate_from(
+            &[
+                "# @typedef Stage 'qa' | 'staging' | 'production'\n\n# @type Stage\nSTAGE=qa\n",
+                "# @typedef Stages Stage[]\n# @type Stages?\nNEXT_PUBLIC_STAGES=qa\n# @type Stage\nSTAGE=qa\n",
+            ],
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(output.contains(
+            "export const Stage = z.enum(['qa','staging','production']);\nexport type Stage = z.infer<typeof Stage>;\n\n\
+             export const Stages = z.string().transform((s) => s.split(\",\").map((e) => e.trim()).filter((e) => e !== \"\")).pipe(z.array(Stage));\n\
+             export type Stages = z.infer<typeof Stages>;\n"
+        ));
+        assert_eq!(output.matches("export const Stage =").count(), 1);
+        assert!(output.contains("    STAGE: Stage /* from \".env.0\" on line 3 */,"));
+        assert!(output.contains("    NEXT_PUBLIC_STAGES: Stages.optional() /*"));
+    }
+
+    #[test]
+    fn zod_schema_gen_with_dates() {
+        let output = generate_from(
+            &["# @type date\nCUTOFF=2024-01-31\n\
+               # @type date(iso)\nRELEASE=2024-01-31\n\
+               # @type datetime(iso)?\nMAINTENANCE=2024-01-31T09:00:00Z\n"],
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(output.contains("    CUTOFF: z.coerce.date() /*"));
+        assert!(output.contains(r"    RELEASE: z.string().regex(/^\d{4}-\d{2}-\d{2}$/) /*"));
+        assert!(
+            output.contains("    MAINTENANCE: z.string().datetime({ offset: true }).optional() /*")
+        );
+    }
+
+    #[test]
+    fn zod_schema_gen_with_quantities() {
+        let output = generate_from(
+            &["# @type duration(s)\nTIMEOUT=30s\n# @type bytes?\nMAX_UPLOAD=512MB\n"],
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(output.contains("    TIMEOUT: duration(\"s\") /*"));
+        assert!(output.contains("    MAX_UPLOAD: bytes(\"B\").optional() /*"));
+        assert!(output.contains("function duration(unit: string) {"));
+    }
+
+    proptest! {
+        #[test]
+        fn generating_never_panics(
+            sources in prop::collection::vec(r#"(([A-Z_]{1,3}[=:] ?|[#@$'"`{}|\\ \n]|type|\PC){0,12}\n?){0,6}"#, 1..3)
+        ) {
+            let sources = sources.iter().map(String::as_str).collect::<Vec<_>>();
+
+            let _ = generate_from(
+                &sources,
+                &Options {
+                    expand: true,
+                    infer: Some(Inference::Aggressive),
+                    ..Default::default()
+                },
+            );
+        }
+    }
 }
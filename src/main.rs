@@ -56,6 +56,44 @@ enum Command {
         /// process.env
         #[arg(long, requires("zod"))]
         node: bool,
+
+        /// Resolve ${VAR}, $VAR and ${VAR:-default} references between the variables, and expand
+        /// them at runtime in the generated zod module.
+        #[arg(long, requires("zod"))]
+        expand: bool,
+
+        /// Infer the types of variables without a type hint from their values across the files.
+        #[arg(
+            long,
+            value_enum,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "conservative"
+        )]
+        infer: Option<dotenv::infer::Inference>,
+
+        /// Fail on warnings, like malformed type hints, instead of just logging them.
+        #[arg(long)]
+        deny_warnings: bool,
+
+        /// Path to the project's config file, with custom types [default: ntro.config.json, if it
+        /// exists]
+        #[arg(long)]
+        config: Option<PathBuf>,
+
+        /// What to do when the .env files disagree on the type hint of a variable.
+        #[arg(long, value_enum, default_value_t)]
+        on_conflict: dotenv::merge::OnConflict,
+
+        /// Layer the .env files like dotenv-flow and Next.js do, e.g. .env.production.local over
+        /// .env.local over .env.production over .env, and know which variables each mode sets.
+        #[arg(long)]
+        profiles: bool,
+
+        /// Print which file wins for each variable when NODE_ENV is the given mode, instead of
+        /// generating anything.
+        #[arg(long, value_name = "NODE_ENV", requires("profiles"))]
+        explain: Option<String>,
     },
     /// Generate a completions file for a specified shell
     Completion {
@@ -112,11 +150,42 @@ fn run(cli: Cli) -> anyhow::Result<()> {
             set_ts_config_path_alias,
             watch,
             node,
+            expand,
+            infer,
+            deny_warnings,
+            config,
+            on_conflict,
+            profiles,
+            explain,
         } => {
+            // the config is loaded for every run, so that watching picks up its changes
+            let options = || -> anyhow::Result<dotenv::Options> {
+                Ok(dotenv::Options {
+                    import_dotenv: node,
+                    expand,
+                    infer,
+                    deny_warnings,
+                    config: dotenv::config::Config::load(config.as_deref())?,
+                    on_conflict,
+                    profiles,
+                })
+            };
+
+            if let Some(mode) = explain {
+                let variables = dotenv::read_variables(&source_files, &options()?)?;
+                println!("{}", dotenv::profile::explain(&variables, &mode)?);
+
+                return Ok(());
+            }
+
             let work = || -> anyhow::Result<()> {
+                let options = options()?;
+                let variables = dotenv::read_variables(&source_files, &options)?;
+
                 if zod {
                     log::info!("starting to generate zod schema for {:?}", source_files);
-                    let content = dotenv::zod::generate_zod_schema(&source_files, node)?;
+                    let content =
+                        dotenv::zod::generate_zod_schema_from_variables(&variables, &options)?;
                     let output_path = output_dir.clone().unwrap_or_default().join("env.parsed.ts");
 
                     write_output(&output_path, content)?;
@@ -148,7 +217,8 @@ fn run(cli: Cli) -> anyhow::Result<()> {
                     "starting to generate typescript declaration files for {:?}",
                     source_files
                 );
-                let content = dotenv::generate_typescript_types(&source_files)?;
+                let content =
+                    dotenv::generate_typescript_types_from_variables(&variables, &options)?;
                 let output_path = output_dir.clone().unwrap_or_default().join("env.d.ts");
 
                 write_output(&output_path, content)?;
@@ -171,7 +241,10 @@ fn run(cli: Cli) -> anyhow::Result<()> {
 
                 work_logging_errors();
 
-                watch::watch(&source_files, work_logging_errors)?;
+                let mut paths = source_files.clone();
+                paths.extend(dotenv::config::Config::path(config.as_deref()));
+
+                watch::watch(&paths, work_logging_errors)?;
             } else {
                 work()?;
             }